@@ -0,0 +1,306 @@
+//! Conditions for `Category: <condition>` rules: file name, size and age.
+
+use std::fs;
+use std::path::Path;
+use std::time::{Duration, SystemTime};
+
+use regex::{Regex, RegexBuilder};
+
+/// A test applied to a file before its extension is looked at.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Condition {
+    /// `name ~ /regex/flags`
+    Name(NamePattern),
+    /// `size > 500MB`
+    Size(Comparison, u64),
+    /// `modified older than 90d` / `modified newer than 7d`
+    Modified { older: bool, age: Duration },
+}
+
+/// A compiled file name regex. Matching runs in time linear in the name, so
+/// no pattern can stall a sort.
+#[derive(Debug, Clone)]
+pub struct NamePattern {
+    regex: Regex,
+    ignore_case: bool,
+}
+
+impl NamePattern {
+    pub fn new(pattern: &str, ignore_case: bool) -> Result<Self, String> {
+        let regex = RegexBuilder::new(pattern)
+            .case_insensitive(ignore_case)
+            .build()
+            .map_err(|err| format!("invalid regex /{}/: {}", pattern, err))?;
+        Ok(Self { regex, ignore_case })
+    }
+
+    pub fn as_str(&self) -> &str {
+        self.regex.as_str()
+    }
+
+    pub fn is_match(&self, text: &str) -> bool {
+        self.regex.is_match(text)
+    }
+}
+
+impl PartialEq for NamePattern {
+    fn eq(&self, other: &Self) -> bool {
+        self.as_str() == other.as_str() && self.ignore_case == other.ignore_case
+    }
+}
+
+impl Eq for NamePattern {}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Comparison {
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+}
+
+impl Condition {
+    /// Whether `rule` looks like a condition (as opposed to an extension list).
+    pub fn is_condition(rule: &str) -> bool {
+        let keyword = rule
+            .trim_start()
+            .split(|c: char| !c.is_ascii_alphabetic())
+            .next()
+            .unwrap_or("");
+        matches!(keyword, "name" | "size" | "modified")
+    }
+
+    /// Parses a condition, returning it and whatever text follows it.
+    pub fn parse(rule: &str) -> Result<(Self, &str), String> {
+        let rule = rule.trim_start();
+        if let Some(rest) = rule.strip_prefix("name") {
+            let rest = rest
+                .trim_start()
+                .strip_prefix('~')
+                .ok_or("expected `name ~ /pattern/`")?;
+            parse_name(rest.trim_start())
+        } else if let Some(rest) = rule.strip_prefix("size") {
+            parse_size(rest.trim_start())
+        } else if let Some(rest) = rule.strip_prefix("modified") {
+            parse_modified(rest.trim_start())
+        } else {
+            Err(format!("unknown condition `{}`", rule))
+        }
+    }
+
+    /// Parses a condition that must make up all of `rule`.
+    pub fn parse_complete(rule: &str) -> Result<Self, String> {
+        match Self::parse(rule)? {
+            (condition, "") => Ok(condition),
+            (_, rest) => Err(format!("unexpected `{}`", rest.trim())),
+        }
+    }
+
+    /// Checks `path`; metadata is only read for size and age conditions.
+    pub fn matches(&self, path: &Path) -> bool {
+        match self {
+            Condition::Name(regex) => path
+                .file_name()
+                .is_some_and(|name| regex.is_match(&name.to_string_lossy())),
+            Condition::Size(comparison, limit) => {
+                let Ok(metadata) = fs::metadata(path) else {
+                    return false;
+                };
+                let size = metadata.len();
+                match comparison {
+                    Comparison::Less => size < *limit,
+                    Comparison::LessOrEqual => size <= *limit,
+                    Comparison::Greater => size > *limit,
+                    Comparison::GreaterOrEqual => size >= *limit,
+                }
+            }
+            Condition::Modified { older, age } => {
+                let Ok(modified) = fs::metadata(path).and_then(|m| m.modified()) else {
+                    return false;
+                };
+                // Files dated in the future count as brand new.
+                let elapsed = SystemTime::now()
+                    .duration_since(modified)
+                    .unwrap_or_default();
+                if *older {
+                    elapsed > *age
+                } else {
+                    elapsed < *age
+                }
+            }
+        }
+    }
+}
+
+/// `/regex/flags`; a `/` inside the pattern is written `\/`.
+fn parse_name(rule: &str) -> Result<(Condition, &str), String> {
+    let body = rule
+        .strip_prefix('/')
+        .ok_or("expected a pattern like /^INV-\\d+/")?;
+    let mut escaped = false;
+    let end = body
+        .char_indices()
+        .find(|&(_, c)| {
+            let close = c == '/' && !escaped;
+            escaped = c == '\\' && !escaped;
+            close
+        })
+        .map(|(idx, _)| idx)
+        .ok_or("missing closing `/`")?;
+    let pattern = &body[..end];
+    let rest = &body[end + 1..];
+    let flags_end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+    let (flags, rest) = rest.split_at(flags_end);
+
+    let mut ignore_case = false;
+    for flag in flags.chars() {
+        match flag {
+            'i' => ignore_case = true,
+            other => return Err(format!("unknown flag `{}`", other)),
+        }
+    }
+
+    let pattern = NamePattern::new(pattern, ignore_case)?;
+    Ok((Condition::Name(pattern), rest))
+}
+
+/// `> 500MB`, `<= 10 KB`, ...
+fn parse_size(rule: &str) -> Result<(Condition, &str), String> {
+    let (comparison, rest) = if let Some(rest) = rule.strip_prefix(">=") {
+        (Comparison::GreaterOrEqual, rest)
+    } else if let Some(rest) = rule.strip_prefix("<=") {
+        (Comparison::LessOrEqual, rest)
+    } else if let Some(rest) = rule.strip_prefix('>') {
+        (Comparison::Greater, rest)
+    } else if let Some(rest) = rule.strip_prefix('<') {
+        (Comparison::Less, rest)
+    } else {
+        return Err("expected `size > <amount>` (or <, >=, <=)".to_string());
+    };
+    let (amount, unit, rest) = split_amount(rest)?;
+    let multiplier: u64 = match unit.to_ascii_uppercase().as_str() {
+        "" | "B" => 1,
+        "KB" => 1024,
+        "MB" => 1024 * 1024,
+        "GB" => 1024 * 1024 * 1024,
+        "TB" => 1024 * 1024 * 1024 * 1024,
+        _ => {
+            return Err(format!(
+                "unknown size unit `{}` (use B, KB, MB, GB or TB)",
+                unit
+            ))
+        }
+    };
+    let limit = amount.checked_mul(multiplier).ok_or("size is too large")?;
+    Ok((Condition::Size(comparison, limit), rest))
+}
+
+/// `older than 90d`, `newer than 12h`, ...
+fn parse_modified(rule: &str) -> Result<(Condition, &str), String> {
+    let (older, rest) = if let Some(rest) = rule.strip_prefix("older") {
+        (true, rest)
+    } else if let Some(rest) = rule.strip_prefix("newer") {
+        (false, rest)
+    } else {
+        return Err(
+            "expected `modified older than <age>` or `modified newer than <age>`".to_string(),
+        );
+    };
+    let rest = rest
+        .trim_start()
+        .strip_prefix("than")
+        .ok_or("expected `than` after `older` / `newer`")?;
+    let (amount, unit, rest) = split_amount(rest)?;
+    let seconds: u64 = match unit {
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        "w" => 7 * 24 * 60 * 60,
+        "y" => 365 * 24 * 60 * 60,
+        _ => return Err(format!("unknown age unit `{}` (use h, d, w or y)", unit)),
+    };
+    let age = amount.checked_mul(seconds).ok_or("age is too large")?;
+    Ok((
+        Condition::Modified {
+            older,
+            age: Duration::from_secs(age),
+        },
+        rest,
+    ))
+}
+
+/// Splits `" 500MB rest"` into `(500, "MB", " rest")`.
+fn split_amount(rule: &str) -> Result<(u64, &str, &str), String> {
+    let rule = rule.trim_start();
+    let digits_end = rule
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(rule.len());
+    let amount = rule[..digits_end]
+        .parse()
+        .map_err(|_| format!("expected a number, found `{}`", rule))?;
+    let rest = rule[digits_end..].trim_start();
+    let unit_end = rest
+        .find(|c: char| !c.is_ascii_alphabetic())
+        .unwrap_or(rest.len());
+    Ok((amount, &rest[..unit_end], &rest[unit_end..]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Comparison, Condition, NamePattern};
+    use std::time::{Duration, Instant};
+
+    fn re(pattern: &str) -> NamePattern {
+        NamePattern::new(pattern, false).unwrap()
+    }
+
+    #[test]
+    fn matches_name_patterns() {
+        assert!(re(r"^INV-\d+").is_match("INV-2024-001.pdf"));
+        assert!(!re(r"^INV-\d+").is_match("old INV-1.pdf"));
+        assert!(re(r"IMG_[0-9]{4}\.jpe?g$").is_match("IMG_1234.jpeg"));
+        assert!(!re(r"IMG_[0-9]{4}\.jpe?g$").is_match("IMG_123.jpg"));
+        assert!(re(r"a\/b").is_match("a/b"));
+        assert!(!re("^scan").is_match("SCAN_01.pdf"));
+        assert!(NamePattern::new("^scan", true)
+            .unwrap()
+            .is_match("SCAN_01.pdf"));
+        assert!(NamePattern::new("(abc", false).is_err());
+    }
+
+    #[test]
+    fn nested_quantifiers_stay_fast() {
+        let pattern = re(r"^(\w+)+\.pdf$");
+        let name = "a".repeat(64);
+        let start = Instant::now();
+        assert!(!pattern.is_match(&name));
+        assert!(start.elapsed() < Duration::from_secs(1));
+    }
+
+    #[test]
+    fn parses_size_and_age() {
+        match Condition::parse("size > 500MB").unwrap() {
+            (Condition::Size(Comparison::Greater, limit), rest) => {
+                assert_eq!(limit, 500 * 1024 * 1024);
+                assert_eq!(rest, "");
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+        match Condition::parse("modified older than 90d priority 2").unwrap() {
+            (Condition::Modified { older, age }, rest) => {
+                assert!(older);
+                assert_eq!(age, Duration::from_secs(90 * 24 * 60 * 60));
+                assert_eq!(rest.trim(), "priority 2");
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+        assert!(Condition::parse("size > 5 parsecs").is_err());
+        assert!(Condition::parse("modified older 9d").is_err());
+    }
+
+    #[test]
+    fn recognises_conditions() {
+        assert!(Condition::is_condition(" name ~ /x/"));
+        assert!(Condition::is_condition("size>1GB"));
+        assert!(!Condition::is_condition("jpg,png"));
+    }
+}
@@ -0,0 +1,169 @@
+//! Policies for planned moves whose target already exists.
+
+use std::fmt;
+use std::fs;
+use std::io;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+/// What to do when a planned target already exists.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ConflictPolicy {
+    /// Leave the source where it is.
+    #[default]
+    Skip,
+    /// Move the source under a free `name (1).ext` style name.
+    Rename,
+    /// Replace the target only when the source was modified more recently.
+    OverwriteIfNewer,
+    /// Replace the target.
+    Overwrite,
+    /// Drop the source when it is identical to the target, otherwise skip it.
+    Dedupe,
+}
+
+impl ConflictPolicy {
+    /// Parses a policy name such as `overwrite-if-newer`.
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "skip" => Some(ConflictPolicy::Skip),
+            "rename" => Some(ConflictPolicy::Rename),
+            "overwrite-if-newer" => Some(ConflictPolicy::OverwriteIfNewer),
+            "overwrite" => Some(ConflictPolicy::Overwrite),
+            "dedupe" => Some(ConflictPolicy::Dedupe),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for ConflictPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            ConflictPolicy::Skip => "skip",
+            ConflictPolicy::Rename => "rename",
+            ConflictPolicy::OverwriteIfNewer => "overwrite-if-newer",
+            ConflictPolicy::Overwrite => "overwrite",
+            ConflictPolicy::Dedupe => "dedupe",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// What `resolve` decided for a single planned move.
+pub enum Resolution {
+    /// Move the source to this path (the planned target or a renamed one).
+    Move(PathBuf),
+    /// Move the source over the existing target.
+    Overwrite,
+    /// The target already holds identical content; drop the source.
+    RemoveSource,
+    /// Leave the source where it is.
+    Skip,
+}
+
+/// Decides what happens to `source` under `policy` when `target` may exist.
+pub fn resolve(policy: ConflictPolicy, source: &Path, target: &Path) -> io::Result<Resolution> {
+    if !target.exists() {
+        return Ok(Resolution::Move(target.to_path_buf()));
+    }
+
+    let resolution = match policy {
+        ConflictPolicy::Skip => Resolution::Skip,
+        ConflictPolicy::Rename => Resolution::Move(free_target(target)),
+        ConflictPolicy::Overwrite => Resolution::Overwrite,
+        ConflictPolicy::OverwriteIfNewer => {
+            let source_time = fs::metadata(source)?.modified()?;
+            let target_time = fs::metadata(target)?.modified()?;
+            if source_time > target_time {
+                Resolution::Overwrite
+            } else {
+                Resolution::Skip
+            }
+        }
+        ConflictPolicy::Dedupe => {
+            if files_identical(source, target)? {
+                Resolution::RemoveSource
+            } else {
+                Resolution::Skip
+            }
+        }
+    };
+    Ok(resolution)
+}
+
+/// First `name (n).ext` next to `target` that does not exist yet.
+fn free_target(target: &Path) -> PathBuf {
+    let mut n = 1usize;
+    loop {
+        let candidate = numbered_name(target, n);
+        if !candidate.exists() {
+            return candidate;
+        }
+        n += 1;
+    }
+}
+
+pub(crate) fn numbered_name(target: &Path, n: usize) -> PathBuf {
+    let stem = target
+        .file_stem()
+        .map(|s| s.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let name = match target.extension() {
+        Some(ext) => format!("{} ({}).{}", stem, n, ext.to_string_lossy()),
+        None => format!("{} ({})", stem, n),
+    };
+    target.with_file_name(name)
+}
+
+/// Whether two files have the same size and byte-for-byte content.
+pub fn files_identical(a: &Path, b: &Path) -> io::Result<bool> {
+    if fs::metadata(a)?.len() != fs::metadata(b)?.len() {
+        return Ok(false);
+    }
+
+    let mut file_a = fs::File::open(a)?;
+    let mut file_b = fs::File::open(b)?;
+    let mut buf_a = [0u8; 8192];
+    let mut buf_b = [0u8; 8192];
+    loop {
+        let read = file_a.read(&mut buf_a)?;
+        if read == 0 {
+            return Ok(true);
+        }
+        file_b.read_exact(&mut buf_b[..read])?;
+        if buf_a[..read] != buf_b[..read] {
+            return Ok(false);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{numbered_name, ConflictPolicy};
+    use std::path::{Path, PathBuf};
+
+    #[test]
+    fn numbered_names_keep_extension() {
+        assert_eq!(
+            numbered_name(Path::new("Documents/report.pdf"), 1),
+            PathBuf::from("Documents/report (1).pdf")
+        );
+        assert_eq!(
+            numbered_name(Path::new("Others/Makefile"), 2),
+            PathBuf::from("Others/Makefile (2)")
+        );
+    }
+
+    #[test]
+    fn parse_policies() {
+        assert_eq!(
+            ConflictPolicy::parse("Rename"),
+            Some(ConflictPolicy::Rename)
+        );
+        assert_eq!(
+            ConflictPolicy::parse("overwrite-if-newer"),
+            Some(ConflictPolicy::OverwriteIfNewer)
+        );
+        assert_eq!(ConflictPolicy::parse("merge"), None);
+    }
+}
@@ -0,0 +1,288 @@
+//! Finding files with identical content and planning to set the extras aside.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use crate::conflict::files_identical;
+use crate::organizer::{self, MoveError, MovePlan};
+
+/// Folder (inside the destination) that duplicates are moved into.
+pub const DUPLICATES_DIR: &str = "Duplicates";
+
+/// Which file of a duplicate group stays where it is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Keep {
+    /// The file modified longest ago, usually the original.
+    #[default]
+    Oldest,
+    /// The most recently modified file.
+    Newest,
+    /// The file with the shortest name, e.g. `photo.jpg` over `photo (1).jpg`.
+    Shortest,
+}
+
+impl Keep {
+    /// Parses `oldest`, `newest` or `shortest`.
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "oldest" => Some(Keep::Oldest),
+            "newest" => Some(Keep::Newest),
+            "shortest" => Some(Keep::Shortest),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Keep {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Keep::Oldest => "oldest",
+            Keep::Newest => "newest",
+            Keep::Shortest => "shortest",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Files with identical content; `files[0]` is the one to keep.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DuplicateGroup {
+    /// Size of each file in bytes.
+    pub size: u64,
+    /// The identical files, starting with the one to keep.
+    pub files: Vec<PathBuf>,
+}
+
+impl DuplicateGroup {
+    /// The file that stays.
+    pub fn keep(&self) -> &Path {
+        &self.files[0]
+    }
+
+    /// The files that are removed or moved aside.
+    pub fn extras(&self) -> &[PathBuf] {
+        &self.files[1..]
+    }
+}
+
+/// What `find_duplicates` found.
+#[derive(Debug, Default)]
+pub struct Duplicates {
+    /// Groups of two or more identical files.
+    pub groups: Vec<DuplicateGroup>,
+    /// Files that could not be read; they are left out of every group.
+    pub errors: Vec<MoveError>,
+}
+
+/// Groups `files` by content: only files of equal size are hashed, and files
+/// with equal hashes are compared byte by byte before they are grouped.
+/// Empty files are never reported, and unreadable files are skipped and
+/// listed in `errors`.
+pub fn find_duplicates(files: &[PathBuf], keep: Keep) -> Duplicates {
+    let mut errors = Vec::new();
+    let mut by_size: HashMap<u64, Vec<&PathBuf>> = HashMap::new();
+    for file in files {
+        match fs::metadata(file) {
+            Ok(metadata) if metadata.len() > 0 => {
+                by_size.entry(metadata.len()).or_default().push(file)
+            }
+            Ok(_) => {}
+            Err(err) => errors.push(unreadable(file, err)),
+        }
+    }
+
+    let mut groups = Vec::new();
+    for (size, candidates) in by_size {
+        if candidates.len() < 2 {
+            continue;
+        }
+        let mut by_hash: HashMap<u64, Vec<&PathBuf>> = HashMap::new();
+        for file in candidates {
+            match hash_file(file) {
+                Ok(hash) => by_hash.entry(hash).or_default().push(file),
+                Err(err) => errors.push(unreadable(file, err)),
+            }
+        }
+        for same_hash in by_hash.into_values() {
+            for mut files in confirm_identical(same_hash, &mut errors) {
+                if files.len() < 2 {
+                    continue;
+                }
+                order_for_keep(&mut files, keep);
+                groups.push(DuplicateGroup { size, files });
+            }
+        }
+    }
+    groups.sort_by(|a, b| a.keep().cmp(b.keep()));
+    errors.sort_by(|a, b| a.source.cmp(&b.source));
+    Duplicates { groups, errors }
+}
+
+/// Bytes freed by removing every extra copy.
+pub fn reclaimable(groups: &[DuplicateGroup]) -> u64 {
+    groups
+        .iter()
+        .map(|group| group.size * group.extras().len() as u64)
+        .sum()
+}
+
+/// Plans moving every extra copy to `<dest>/Duplicates/`, numbering names
+/// that are already taken.
+pub fn plan_duplicates(dest_dir: &Path, groups: &[DuplicateGroup]) -> Vec<MovePlan> {
+    let extras = groups.iter().flat_map(DuplicateGroup::extras);
+    organizer::plan_into_folder(dest_dir, DUPLICATES_DIR, extras)
+}
+
+/// Splits files with equal hashes into groups of truly identical files.
+fn confirm_identical(files: Vec<&PathBuf>, errors: &mut Vec<MoveError>) -> Vec<Vec<PathBuf>> {
+    let mut groups: Vec<Vec<PathBuf>> = Vec::new();
+    'files: for file in files {
+        for group in &mut groups {
+            match files_identical(&group[0], file) {
+                Ok(true) => {
+                    group.push(file.clone());
+                    continue 'files;
+                }
+                Ok(false) => {}
+                // The group's first file was just read in full, so blame this one.
+                Err(err) => {
+                    errors.push(unreadable(file, err));
+                    continue 'files;
+                }
+            }
+        }
+        groups.push(vec![file.clone()]);
+    }
+    groups
+}
+
+fn unreadable(file: &Path, err: io::Error) -> MoveError {
+    MoveError {
+        source: file.to_path_buf(),
+        reason: err.to_string(),
+    }
+}
+
+fn order_for_keep(files: &mut [PathBuf], keep: Keep) {
+    let modified = |path: &PathBuf| {
+        fs::metadata(path)
+            .and_then(|m| m.modified())
+            .unwrap_or(SystemTime::UNIX_EPOCH)
+    };
+    let name_len = |path: &PathBuf| path.file_name().map_or(0, |name| name.len());
+    files.sort();
+    match keep {
+        Keep::Oldest => files.sort_by_key(modified),
+        Keep::Newest => files.sort_by_key(|path| std::cmp::Reverse(modified(path))),
+        Keep::Shortest => files.sort_by_key(name_len),
+    }
+}
+
+/// 64-bit FNV-1a over the whole file.
+fn hash_file(path: &Path) -> io::Result<u64> {
+    let mut file = fs::File::open(path)?;
+    let mut buf = [0u8; 64 * 1024];
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    loop {
+        let read = file.read(&mut buf)?;
+        if read == 0 {
+            return Ok(hash);
+        }
+        for &byte in &buf[..read] {
+            hash ^= u64::from(byte);
+            hash = hash.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{find_duplicates, plan_duplicates, Keep};
+    use std::env;
+    use std::fs;
+
+    #[test]
+    fn groups_identical_files_and_plans_extras() {
+        let dir = env::temp_dir().join(format!("rusty-sort-dedupe-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("sub")).unwrap();
+        let write = |name: &str, content: &str| {
+            let path = dir.join(name);
+            fs::write(&path, content).unwrap();
+            path
+        };
+        let files = vec![
+            write("photo (1).jpg", "same bytes"),
+            write("photo.jpg", "same bytes"),
+            write("sub/photo.jpg", "same bytes"),
+            write("other.jpg", "diff bytes"),
+            write("empty-a.txt", ""),
+            write("empty-b.txt", ""),
+        ];
+
+        let found = find_duplicates(&files, Keep::Shortest);
+        assert!(found.errors.is_empty());
+        let groups = found.groups;
+        assert_eq!(groups.len(), 1);
+        assert_eq!(groups[0].files.len(), 3);
+        assert_eq!(groups[0].keep(), dir.join("photo.jpg"));
+
+        let plans = plan_duplicates(&dir, &groups);
+        let targets: Vec<_> = plans.iter().map(|plan| plan.target.clone()).collect();
+        assert_eq!(
+            targets,
+            [
+                dir.join("Duplicates").join("photo.jpg"),
+                dir.join("Duplicates").join("photo (1).jpg"),
+            ]
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn skips_and_reports_unreadable_files() {
+        let dir = env::temp_dir().join(format!("rusty-sort-unreadable-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let write = |name: &str| {
+            let path = dir.join(name);
+            fs::write(&path, "same bytes").unwrap();
+            path
+        };
+        let files = vec![
+            write("a.jpg"),
+            write("b.jpg"),
+            write("locked.jpg"),
+            dir.join("vanished.jpg"),
+        ];
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            fs::set_permissions(&files[2], fs::Permissions::from_mode(0o000)).unwrap();
+        }
+        // Root (and Windows) can still read a file without permissions.
+        let locked = fs::File::open(&files[2]).is_err();
+
+        let found = find_duplicates(&files, Keep::Oldest);
+        let failed: Vec<_> = found.errors.iter().map(|e| e.source.clone()).collect();
+        let mut expected = vec![dir.join("vanished.jpg")];
+        if locked {
+            expected.insert(0, dir.join("locked.jpg"));
+        }
+        assert_eq!(failed, expected);
+        assert_eq!(found.groups.len(), 1);
+        assert_eq!(found.groups[0].files.len(), if locked { 2 } else { 3 });
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn parses_keep_policies() {
+        assert_eq!(Keep::parse("Newest"), Some(Keep::Newest));
+        assert_eq!(Keep::parse("biggest"), None);
+        assert_eq!(Keep::default().to_string(), "oldest");
+    }
+}
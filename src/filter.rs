@@ -0,0 +1,279 @@
+//! Include/exclude glob patterns applied while scanning.
+
+use std::io;
+use std::path::Path;
+
+/// A glob pattern: `*` and `?` stay within one path segment, `**` spans
+/// segments, and `[abc]`, `[a-z]`, `[!x]` match character classes.
+///
+/// Patterns without a `/` are matched against the file name. Patterns with a
+/// `/` are matched against the path relative to the scanned folder, starting
+/// at any folder unless they begin with `/`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Glob {
+    pattern: String,
+    chars: Vec<char>,
+    anchored: bool,
+    by_name: bool,
+}
+
+impl Glob {
+    /// Parses a pattern; backslashes are treated as `/`.
+    pub fn parse(pattern: &str) -> io::Result<Self> {
+        let trimmed = pattern.trim().replace('\\', "/");
+        if trimmed.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "Empty glob pattern",
+            ));
+        }
+        let anchored = trimmed.starts_with('/');
+        let body = trimmed.trim_start_matches('/').trim_end_matches('/');
+        Ok(Self {
+            pattern: trimmed.clone(),
+            chars: body.chars().collect(),
+            anchored,
+            by_name: !body.contains('/'),
+        })
+    }
+
+    /// The pattern as written.
+    pub fn as_str(&self) -> &str {
+        &self.pattern
+    }
+
+    /// Matches a path relative to the scanned folder, using `/` separators.
+    pub fn matches(&self, rel: &str) -> bool {
+        if self.by_name {
+            let name = rel.rsplit('/').next().unwrap_or(rel);
+            return glob_match(&self.chars, &name.chars().collect::<Vec<_>>());
+        }
+
+        let rel: Vec<char> = rel.chars().collect();
+        if self.anchored {
+            return glob_match(&self.chars, &rel);
+        }
+        (0..rel.len())
+            .filter(|&i| i == 0 || rel[i - 1] == '/')
+            .any(|i| glob_match(&self.chars, &rel[i..]))
+    }
+
+    /// Whether every path below the folder `rel` is matched, so the walk can
+    /// skip it (e.g. `node_modules/**`).
+    pub fn matches_everything_under(&self, rel: &str) -> bool {
+        let Some(prefix) = self.chars.strip_suffix(&['/', '*', '*']) else {
+            return false;
+        };
+        let rel: Vec<char> = rel.chars().collect();
+        if self.anchored {
+            return glob_match(prefix, &rel);
+        }
+        (0..rel.len())
+            .filter(|&i| i == 0 || rel[i - 1] == '/')
+            .any(|i| glob_match(prefix, &rel[i..]))
+    }
+}
+
+/// Exclude patterns always win; if any include patterns exist, a file must
+/// match one of them to be scanned.
+#[derive(Debug, Clone, Default)]
+pub struct Filter {
+    excludes: Vec<Glob>,
+    includes: Vec<Glob>,
+}
+
+impl Filter {
+    /// Skips files and folders matching `glob`.
+    pub fn exclude(&mut self, glob: Glob) {
+        self.excludes.push(glob);
+    }
+
+    /// Only scans files matching `glob` (or any other include pattern).
+    pub fn include(&mut self, glob: Glob) {
+        self.includes.push(glob);
+    }
+
+    /// Whether no patterns were added.
+    pub fn is_empty(&self) -> bool {
+        self.excludes.is_empty() && self.includes.is_empty()
+    }
+
+    /// Whether the file at `path` (inside `base_dir`) should be scanned.
+    pub fn allows_file(&self, base_dir: &Path, path: &Path) -> bool {
+        let rel = relative(base_dir, path);
+        if self.excludes.iter().any(|glob| glob.matches(&rel)) {
+            return false;
+        }
+        self.includes.is_empty() || self.includes.iter().any(|glob| glob.matches(&rel))
+    }
+
+    /// Whether the walk should descend into the folder at `path`.
+    pub fn allows_dir(&self, base_dir: &Path, path: &Path) -> bool {
+        let rel = relative(base_dir, path);
+        !self
+            .excludes
+            .iter()
+            .any(|glob| glob.matches_everything_under(&rel))
+    }
+}
+
+fn relative(base_dir: &Path, path: &Path) -> String {
+    let rel = path.strip_prefix(base_dir).unwrap_or(path);
+    rel.components()
+        .map(|c| c.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// Matches by trying each way a `*` can end; results are remembered per
+/// (pattern, text) position, so patterns with many stars stay fast.
+fn glob_match(pattern: &[char], text: &[char]) -> bool {
+    let mut matcher = Matcher {
+        pattern,
+        text,
+        memo: vec![None; (pattern.len() + 1) * (text.len() + 1)],
+    };
+    matcher.matches(0, 0)
+}
+
+struct Matcher<'a> {
+    pattern: &'a [char],
+    text: &'a [char],
+    memo: Vec<Option<bool>>,
+}
+
+impl Matcher<'_> {
+    /// Whether `pattern[p..]` matches `text[t..]`.
+    fn matches(&mut self, p: usize, t: usize) -> bool {
+        let key = p * (self.text.len() + 1) + t;
+        if let Some(matched) = self.memo[key] {
+            return matched;
+        }
+        let matched = self.match_uncached(p, t);
+        self.memo[key] = Some(matched);
+        matched
+    }
+
+    fn match_uncached(&mut self, p: usize, t: usize) -> bool {
+        let (pattern, text) = (self.pattern, self.text);
+        match pattern.get(p) {
+            None => t == text.len(),
+            Some('*') if pattern.get(p + 1) == Some(&'*') => {
+                // `**/` may also match no folders at all.
+                if pattern.get(p + 2) == Some(&'/') && self.matches(p + 3, t) {
+                    return true;
+                }
+                (t..=text.len()).any(|i| self.matches(p + 2, i))
+            }
+            Some('*') => {
+                for i in t..=text.len() {
+                    if self.matches(p + 1, i) {
+                        return true;
+                    }
+                    if i < text.len() && text[i] == '/' {
+                        break;
+                    }
+                }
+                false
+            }
+            Some('?') => match text.get(t) {
+                Some(&c) if c != '/' => self.matches(p + 1, t + 1),
+                _ => false,
+            },
+            Some('[') => {
+                let Some(end) = pattern[p..]
+                    .iter()
+                    .skip(2)
+                    .position(|&c| c == ']')
+                    .map(|i| p + i + 2)
+                else {
+                    // No closing bracket: treat `[` literally.
+                    return text.get(t) == Some(&'[') && self.matches(p + 1, t + 1);
+                };
+                match text.get(t) {
+                    Some(&c) if c != '/' && class_matches(&pattern[p + 1..end], c) => {
+                        self.matches(end + 1, t + 1)
+                    }
+                    _ => false,
+                }
+            }
+            Some(&c) => text.get(t) == Some(&c) && self.matches(p + 1, t + 1),
+        }
+    }
+}
+
+fn class_matches(class: &[char], c: char) -> bool {
+    let (negated, class) = match class.first() {
+        Some('!') | Some('^') => (true, &class[1..]),
+        _ => (false, class),
+    };
+    let mut found = false;
+    let mut i = 0;
+    while i < class.len() {
+        if i + 2 < class.len() && class[i + 1] == '-' {
+            if class[i] <= c && c <= class[i + 2] {
+                found = true;
+            }
+            i += 3;
+        } else {
+            if class[i] == c {
+                found = true;
+            }
+            i += 1;
+        }
+    }
+    found != negated
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Filter, Glob};
+    use std::path::Path;
+    use std::time::{Duration, Instant};
+
+    fn glob(pattern: &str) -> Glob {
+        Glob::parse(pattern).unwrap()
+    }
+
+    #[test]
+    fn name_patterns() {
+        assert!(glob("*.part").matches("downloads/movie.mkv.part"));
+        assert!(glob("~$*").matches("~$report.docx"));
+        assert!(!glob("*.part").matches("partial.txt"));
+        assert!(glob("IMG_[0-9]???.jpg").matches("IMG_1234.jpg"));
+        assert!(!glob("[!I]*.jpg").matches("IMG_1234.jpg"));
+    }
+
+    #[test]
+    fn path_patterns() {
+        assert!(glob("node_modules/**").matches("node_modules/a/b.js"));
+        assert!(glob("node_modules/**").matches("web/node_modules/a.js"));
+        assert!(!glob("/node_modules/**").matches("web/node_modules/a.js"));
+        assert!(glob("**/*.tmp").matches("a.tmp"));
+        assert!(glob("docs/*.md").matches("docs/readme.md"));
+        assert!(!glob("docs/*.md").matches("docs/sub/readme.md"));
+    }
+
+    #[test]
+    fn many_stars_stay_fast() {
+        let pattern = glob("*a*a*a*a*a*a*a*b");
+        let name = "a".repeat(40);
+        let start = Instant::now();
+        assert!(!pattern.matches(&name));
+        assert!(!glob("**a**a**a**a**a**a**b").matches(&format!("{}/{}", name, name)));
+        assert!(start.elapsed() < Duration::from_secs(1));
+        assert!(pattern.matches(&format!("{}b", name)));
+    }
+
+    #[test]
+    fn filter_prunes_excluded_dirs() {
+        let mut filter = Filter::default();
+        filter.exclude(glob(".git/**"));
+        filter.include(glob("*.jpg"));
+        let base = Path::new("src");
+        assert!(!filter.allows_dir(base, Path::new("src/.git")));
+        assert!(filter.allows_dir(base, Path::new("src/photos")));
+        assert!(filter.allows_file(base, Path::new("src/photos/a.jpg")));
+        assert!(!filter.allows_file(base, Path::new("src/notes.txt")));
+    }
+}
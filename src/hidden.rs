@@ -0,0 +1,184 @@
+//! Hidden files and OS junk files such as `.DS_Store` and `Thumbs.db`.
+
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::organizer::MoveError;
+
+/// Category that hidden files are sorted into with `HiddenPolicy::Category`.
+pub const HIDDEN_CATEGORY: &str = "Hidden";
+
+/// Folder (inside the destination) that junk files are quarantined in.
+pub const QUARANTINE_DIR: &str = "Quarantine";
+
+/// Files operating systems leave behind; matched case-insensitively.
+const JUNK_FILES: &[&str] = &[
+    ".DS_Store",
+    "Thumbs.db",
+    "ehthumbs.db",
+    "desktop.ini",
+    ".directory",
+];
+
+/// What a scan does with hidden files and folders.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum HiddenPolicy {
+    /// Leave them where they are and don't descend into hidden folders.
+    #[default]
+    Skip,
+    /// Sort them like any other file.
+    Include,
+    /// Sort hidden files into the `Hidden` category; hidden folders are still
+    /// not descended into.
+    Category,
+}
+
+impl HiddenPolicy {
+    /// Parses `skip`, `include` or `category`.
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "skip" => Some(HiddenPolicy::Skip),
+            "include" => Some(HiddenPolicy::Include),
+            "category" => Some(HiddenPolicy::Category),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for HiddenPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            HiddenPolicy::Skip => "skip",
+            HiddenPolicy::Include => "include",
+            HiddenPolicy::Category => "category",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// What happens to OS junk files instead of sorting them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum JunkPolicy {
+    /// Leave them where they are.
+    #[default]
+    Skip,
+    /// Treat them like any other file (subject to the hidden-file policy).
+    Sort,
+    /// Delete them.
+    Delete,
+    /// Move them into `<dest>/Quarantine/`.
+    Quarantine,
+}
+
+impl JunkPolicy {
+    /// Parses `skip`, `sort`, `delete` or `quarantine`.
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "skip" => Some(JunkPolicy::Skip),
+            "sort" => Some(JunkPolicy::Sort),
+            "delete" => Some(JunkPolicy::Delete),
+            "quarantine" => Some(JunkPolicy::Quarantine),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for JunkPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            JunkPolicy::Skip => "skip",
+            JunkPolicy::Sort => "sort",
+            JunkPolicy::Delete => "delete",
+            JunkPolicy::Quarantine => "quarantine",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Dotfiles and dot-folders, plus anything with the hidden attribute on Windows.
+pub fn is_hidden(path: &Path) -> bool {
+    let dotfile = path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .is_some_and(|name| name.starts_with('.'));
+    dotfile || has_hidden_attribute(path)
+}
+
+#[cfg(windows)]
+fn has_hidden_attribute(path: &Path) -> bool {
+    use std::os::windows::fs::MetadataExt;
+
+    const FILE_ATTRIBUTE_HIDDEN: u32 = 0x2;
+    fs::symlink_metadata(path).is_ok_and(|m| m.file_attributes() & FILE_ATTRIBUTE_HIDDEN != 0)
+}
+
+#[cfg(not(windows))]
+fn has_hidden_attribute(_path: &Path) -> bool {
+    false
+}
+
+/// Whether `path` is a known OS junk file, including macOS `._*` resource forks.
+pub fn is_junk(path: &Path) -> bool {
+    let Some(name) = path.file_name().and_then(|name| name.to_str()) else {
+        return false;
+    };
+    name.starts_with("._")
+        || JUNK_FILES
+            .iter()
+            .any(|junk| junk.eq_ignore_ascii_case(name))
+}
+
+/// Deletes `files`, returning how many were deleted and the ones that could
+/// not be. With `fail_fast`, the first failure is returned as an error.
+pub fn delete_junk(files: &[PathBuf], fail_fast: bool) -> io::Result<(usize, Vec<MoveError>)> {
+    let mut deleted = 0;
+    let mut errors = Vec::new();
+    for file in files {
+        match fs::remove_file(file) {
+            Ok(()) => deleted += 1,
+            Err(err) if fail_fast => {
+                return Err(io::Error::new(
+                    err.kind(),
+                    format!("{}: {}", file.display(), err),
+                ));
+            }
+            Err(err) => errors.push(MoveError {
+                source: file.clone(),
+                reason: err.to_string(),
+            }),
+        }
+    }
+    Ok((deleted, errors))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{is_hidden, is_junk, HiddenPolicy, JunkPolicy};
+    use std::path::Path;
+
+    #[test]
+    fn recognises_hidden_and_junk_files() {
+        assert!(is_hidden(Path::new("home/.bashrc")));
+        assert!(!is_hidden(Path::new("home/notes.txt")));
+        assert!(is_junk(Path::new("photos/thumbs.DB")));
+        assert!(is_junk(Path::new("photos/._IMG_0001.jpg")));
+        assert!(is_junk(Path::new(".DS_Store")));
+        assert!(!is_junk(Path::new(".bashrc")));
+    }
+
+    #[test]
+    fn parses_policies() {
+        assert_eq!(
+            HiddenPolicy::parse("Category"),
+            Some(HiddenPolicy::Category)
+        );
+        assert_eq!(
+            JunkPolicy::parse("quarantine"),
+            Some(JunkPolicy::Quarantine)
+        );
+        assert_eq!(JunkPolicy::parse("shred"), None);
+        assert_eq!(HiddenPolicy::default().to_string(), "skip");
+    }
+}
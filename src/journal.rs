@@ -0,0 +1,351 @@
+//! The per-folder journal of completed moves, used by `undo`.
+
+use std::fs;
+use std::io;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::transfer::{self, Mode};
+
+/// The journal's file name inside the destination folder.
+pub const JOURNAL_FILE_NAME: &str = ".rusty-sort-journal.txt";
+
+/// One completed move, copy or link, as recorded after it succeeded.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Entry {
+    /// Id shared by every entry of one run.
+    pub run_id: String,
+    /// When the entry was recorded, in seconds since the Unix epoch.
+    pub timestamp: u64,
+    /// Where the file was before the run.
+    pub source: PathBuf,
+    /// Where the file was moved, copied or linked to.
+    pub target: PathBuf,
+    /// Size of `target` when it was recorded.
+    pub size: u64,
+    /// Modification time of `target` in nanoseconds since the Unix epoch.
+    pub modified: u128,
+    /// Whether the entry was a move, copy or link.
+    pub mode: Mode,
+}
+
+/// Appends moves for a single run to the journal in `base_dir`.
+pub struct Journal {
+    path: PathBuf,
+    run_id: String,
+    file: Option<fs::File>,
+}
+
+impl Journal {
+    /// Starts a new run that writes to the journal in `base_dir`.
+    pub fn new(base_dir: &Path) -> Self {
+        Self {
+            path: journal_path(base_dir),
+            run_id: new_run_id(),
+            file: None,
+        }
+    }
+
+    /// The id written with every entry of this run.
+    pub fn run_id(&self) -> &str {
+        &self.run_id
+    }
+
+    /// Appends a completed transfer, fingerprinting `target` so undo can tell if it changed.
+    pub fn record(&mut self, source: &Path, target: &Path, mode: Mode) -> io::Result<()> {
+        let (size, modified) = fingerprint(target)?;
+        let entry = Entry {
+            run_id: self.run_id.clone(),
+            timestamp: now_secs(),
+            source: std::path::absolute(source)?,
+            target: std::path::absolute(target)?,
+            size,
+            modified,
+            mode,
+        };
+
+        if self.file.is_none() {
+            let file = fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&self.path)?;
+            self.file = Some(file);
+        }
+        if let Some(file) = self.file.as_mut() {
+            writeln!(file, "{}", format_entry(&entry))?;
+        }
+        Ok(())
+    }
+}
+
+/// The journal file inside `base_dir`.
+pub fn journal_path(base_dir: &Path) -> PathBuf {
+    base_dir.join(JOURNAL_FILE_NAME)
+}
+
+/// The journal's contents. Lines that could not be read are kept aside so one
+/// damaged line does not make the rest of the journal unusable.
+#[derive(Debug, Default)]
+pub struct Loaded {
+    /// Entries in the order they were written.
+    pub entries: Vec<Entry>,
+    /// Lines that could not be parsed.
+    pub invalid: Vec<InvalidLine>,
+}
+
+/// A journal line that could not be parsed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InvalidLine {
+    /// 1-based line number in the journal file.
+    pub number: usize,
+    /// The line as it appears in the file.
+    pub text: String,
+}
+
+/// Reads the journal in `base_dir`; a missing journal is empty.
+pub fn load(base_dir: &Path) -> io::Result<Loaded> {
+    let path = journal_path(base_dir);
+    if !path.exists() {
+        return Ok(Loaded::default());
+    }
+
+    let content = fs::read_to_string(&path)?;
+    let mut loaded = Loaded::default();
+    for (idx, line) in content.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        match parse_entry(line) {
+            Some(entry) => loaded.entries.push(entry),
+            None => loaded.invalid.push(InvalidLine {
+                number: idx + 1,
+                text: line.to_string(),
+            }),
+        }
+    }
+    Ok(loaded)
+}
+
+/// Rewrites the journal with `entries`, keeping `invalid` lines verbatim at
+/// the end so they can still be inspected by hand.
+pub fn save(base_dir: &Path, entries: &[Entry], invalid: &[InvalidLine]) -> io::Result<()> {
+    let path = journal_path(base_dir);
+    if entries.is_empty() && invalid.is_empty() {
+        if path.exists() {
+            fs::remove_file(path)?;
+        }
+        return Ok(());
+    }
+
+    let mut lines = String::new();
+    for entry in entries {
+        lines.push_str(&format_entry(entry));
+        lines.push('\n');
+    }
+    for line in invalid {
+        lines.push_str(&line.text);
+        lines.push('\n');
+    }
+    fs::write(path, lines)
+}
+
+/// The id of the most recent run still present in the journal.
+pub fn last_run_id(entries: &[Entry]) -> Option<&str> {
+    entries.last().map(|entry| entry.run_id.as_str())
+}
+
+/// What `undo_entry` did with a journal entry.
+pub enum UndoOutcome {
+    /// The file was moved back to its source.
+    Restored,
+    /// A copy or link was deleted; the original was still in place.
+    Removed,
+    /// The target no longer exists.
+    TargetMissing,
+    /// The target was modified since it was recorded.
+    TargetChanged,
+    /// Something else already exists at the source path.
+    SourceExists,
+}
+
+/// Moves a journaled file back, refusing if the target is gone or was modified.
+/// Copies and links are deleted instead while the original still exists.
+pub fn undo_entry(entry: &Entry) -> io::Result<UndoOutcome> {
+    if !entry.target.exists() {
+        return Ok(UndoOutcome::TargetMissing);
+    }
+    if fingerprint(&entry.target)? != (entry.size, entry.modified) {
+        return Ok(UndoOutcome::TargetChanged);
+    }
+    if entry.mode != Mode::Move && entry.source.exists() {
+        fs::remove_file(&entry.target)?;
+        return Ok(UndoOutcome::Removed);
+    }
+    if entry.source.exists() {
+        return Ok(UndoOutcome::SourceExists);
+    }
+    if let Some(parent) = entry.source.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    transfer::move_file(&entry.target, &entry.source)?;
+    Ok(UndoOutcome::Restored)
+}
+
+fn fingerprint(path: &Path) -> io::Result<(u64, u128)> {
+    let metadata = fs::metadata(path)?;
+    let modified = metadata
+        .modified()?
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_nanos())
+        .unwrap_or(0);
+    Ok((metadata.len(), modified))
+}
+
+fn format_entry(entry: &Entry) -> String {
+    format!(
+        "{}\t{}\t{}\t{}\t{}\t{}\t{}",
+        entry.run_id,
+        entry.timestamp,
+        escape(&entry.source.to_string_lossy()),
+        escape(&entry.target.to_string_lossy()),
+        entry.size,
+        entry.modified,
+        entry.mode
+    )
+}
+
+fn parse_entry(line: &str) -> Option<Entry> {
+    let mut fields = line.split('\t');
+    let entry = Entry {
+        run_id: fields.next()?.to_string(),
+        timestamp: fields.next()?.parse().ok()?,
+        source: PathBuf::from(unescape(fields.next()?)?),
+        target: PathBuf::from(unescape(fields.next()?)?),
+        size: fields.next()?.parse().ok()?,
+        modified: fields.next()?.parse().ok()?,
+        mode: Mode::parse(fields.next()?)?,
+    };
+    if fields.next().is_some() || entry.run_id.is_empty() {
+        return None;
+    }
+    Some(entry)
+}
+
+/// Escapes backslashes, tabs and line breaks so a path always stays one field.
+fn escape(field: &str) -> String {
+    let mut out = String::with_capacity(field.len());
+    for c in field.chars() {
+        match c {
+            '\\' => out.push_str("\\\\"),
+            '\t' => out.push_str("\\t"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+fn unescape(field: &str) -> Option<String> {
+    let mut out = String::with_capacity(field.len());
+    let mut chars = field.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next()? {
+            '\\' => out.push('\\'),
+            't' => out.push('\t'),
+            'n' => out.push('\n'),
+            'r' => out.push('\r'),
+            _ => return None,
+        }
+    }
+    Some(out)
+}
+
+fn new_run_id() -> String {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    format!("{}-{:03}", now.as_secs(), now.subsec_millis())
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{format_entry, journal_path, load, parse_entry, save, Entry, InvalidLine};
+    use crate::transfer::Mode;
+    use std::env;
+    use std::fs;
+    use std::path::PathBuf;
+
+    #[test]
+    fn entry_round_trip() {
+        let entry = Entry {
+            run_id: "1700000000-042".to_string(),
+            timestamp: 1700000000,
+            source: PathBuf::from("/data/in/report.pdf"),
+            target: PathBuf::from("/data/in/Documents/report.pdf"),
+            size: 1234,
+            modified: 1700000000123456789,
+            mode: Mode::Copy,
+        };
+        assert_eq!(parse_entry(&format_entry(&entry)), Some(entry));
+    }
+
+    #[test]
+    fn entry_round_trip_with_tabs_and_newlines_in_names() {
+        let entry = Entry {
+            run_id: "1700000000-042".to_string(),
+            timestamp: 1700000000,
+            source: PathBuf::from("/data/in/a\tb\\c\nd.txt"),
+            target: PathBuf::from("/data/in/Documents/a\tb\\c\nd.txt"),
+            size: 1,
+            modified: 2,
+            mode: Mode::Move,
+        };
+        let line = format_entry(&entry);
+        assert!(!line.contains('\n'));
+        assert_eq!(line.matches('\t').count(), 6);
+        assert_eq!(parse_entry(&line), Some(entry));
+    }
+
+    #[test]
+    fn load_skips_bad_lines_and_save_keeps_them() {
+        let dir = env::temp_dir().join(format!("rusty-sort-journal-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let good = "run\t1\t/a\t/b\t1\t2\tmove";
+        fs::write(journal_path(&dir), format!("garbage\n{}\n", good)).unwrap();
+
+        let loaded = load(&dir).unwrap();
+        assert_eq!(loaded.entries.len(), 1);
+        assert_eq!(
+            loaded.invalid,
+            vec![InvalidLine {
+                number: 1,
+                text: "garbage".to_string()
+            }]
+        );
+
+        save(&dir, &[], &loaded.invalid).unwrap();
+        assert_eq!(fs::read_to_string(journal_path(&dir)).unwrap(), "garbage\n");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn rejects_malformed_lines() {
+        assert_eq!(parse_entry("run\tnot-a-number\ta\tb\t1\t2"), None);
+        assert_eq!(parse_entry("run\t1\ta\tb"), None);
+        assert_eq!(parse_entry("run\t1\ta\tb\t1\t2"), None);
+    }
+}
@@ -0,0 +1,417 @@
+//! Destination path templates.
+
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::rules::Category;
+
+/// The template used when none is given: `<dest>/<category>/<name>`.
+pub const DEFAULT_LAYOUT: &str = "{category}/{name}";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Field {
+    Category,
+    Name,
+    Stem,
+    Ext,
+    Year,
+    Month,
+    Day,
+    Parent,
+    Dir,
+    Size,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Part {
+    Literal(String),
+    Field(Field),
+}
+
+/// A destination template such as `{category}/{year}/{month}/{name}`,
+/// rendered relative to the destination folder.
+#[derive(Debug, Clone)]
+pub struct Layout {
+    template: String,
+    parts: Vec<Part>,
+    /// The scanned folder `{dir}` is relative to.
+    source_dir: Option<PathBuf>,
+}
+
+impl Layout {
+    /// Parses a template; it must be relative, use known placeholders and
+    /// contain `{name}`.
+    pub fn parse(template: &str) -> io::Result<Self> {
+        let template = template.trim().replace('\\', "/");
+        let invalid = |reason: &str| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("Invalid layout '{}': {}", template, reason),
+            )
+        };
+
+        if template.starts_with('/') || template.contains(':') {
+            return Err(invalid("must be relative to the destination"));
+        }
+        if template.split('/').any(|seg| seg == ".." || seg == ".") {
+            return Err(invalid("'.' and '..' segments are not allowed"));
+        }
+
+        let mut parts = Vec::new();
+        let mut rest = template.as_str();
+        while let Some(open) = rest.find('{') {
+            if open > 0 {
+                parts.push(Part::Literal(rest[..open].to_string()));
+            }
+            let close = rest[open..]
+                .find('}')
+                .map(|idx| open + idx)
+                .ok_or_else(|| invalid("unclosed '{'"))?;
+            let name = &rest[open + 1..close];
+            let field = parse_field(name)
+                .ok_or_else(|| invalid(&format!("unknown placeholder {{{}}}", name)))?;
+            parts.push(Part::Field(field));
+            rest = &rest[close + 1..];
+        }
+        if !rest.is_empty() {
+            parts.push(Part::Literal(rest.to_string()));
+        }
+
+        if !parts.contains(&Part::Field(Field::Name)) {
+            return Err(invalid("must contain {name}"));
+        }
+
+        Ok(Self {
+            template,
+            parts,
+            source_dir: None,
+        })
+    }
+
+    /// Sets the folder that `{dir}` is rendered relative to.
+    pub fn with_source_dir(mut self, source_dir: &Path) -> Self {
+        self.source_dir = Some(source_dir.to_path_buf());
+        self
+    }
+
+    /// Keeps the folders a file was found in: adds a `{dir}` folder in front
+    /// of the one holding `{name}`, unless the template already uses `{dir}`.
+    pub fn keep_structure(self) -> Self {
+        if self.parts.contains(&Part::Field(Field::Dir)) {
+            return self;
+        }
+        let mut segments: Vec<&str> = self.template.split('/').collect();
+        let at = segments
+            .iter()
+            .position(|segment| {
+                let compact: String = segment.chars().filter(|c| !c.is_whitespace()).collect();
+                compact.to_ascii_lowercase().contains("{name}")
+            })
+            .unwrap_or(segments.len() - 1);
+        segments.insert(at, "{dir}");
+        let layout = Self::parse(&segments.join("/")).expect("adding {dir} keeps a layout valid");
+        Self {
+            source_dir: self.source_dir,
+            ..layout
+        }
+    }
+
+    /// Path of `file` under `dest_dir` according to this layout.
+    pub fn target(&self, dest_dir: &Path, file: &Path, category: &Category) -> PathBuf {
+        let date = self.needs_date().then(|| modified_date(file));
+        let mut rendered = String::new();
+        for part in &self.parts {
+            match part {
+                Part::Literal(text) => rendered.push_str(text),
+                Part::Field(field) => rendered.push_str(&render_field(
+                    *field,
+                    file,
+                    category,
+                    date,
+                    self.source_dir.as_deref(),
+                )),
+            }
+        }
+
+        join_segments(dest_dir, &rendered)
+    }
+
+    /// The folder holding every file of `category`, when the template starts
+    /// with fixed folders followed by a `{category}` folder.
+    pub fn category_root(&self, dest_dir: &Path, category: &Category) -> Option<PathBuf> {
+        let mut rendered = String::new();
+        for (idx, part) in self.parts.iter().enumerate() {
+            match part {
+                Part::Literal(text) => rendered.push_str(text),
+                Part::Field(Field::Category) => {
+                    let at_segment_start = rendered.is_empty() || rendered.ends_with('/');
+                    let next_is_separator = matches!(
+                        self.parts.get(idx + 1),
+                        Some(Part::Literal(text)) if text.starts_with('/')
+                    );
+                    if !at_segment_start || !next_is_separator {
+                        return None;
+                    }
+                    rendered.push_str(category.name());
+                    return Some(join_segments(dest_dir, &rendered));
+                }
+                Part::Field(_) => return None,
+            }
+        }
+        None
+    }
+
+    fn needs_date(&self) -> bool {
+        self.parts.iter().any(|part| {
+            matches!(
+                part,
+                Part::Field(Field::Year) | Part::Field(Field::Month) | Part::Field(Field::Day)
+            )
+        })
+    }
+}
+
+impl Default for Layout {
+    fn default() -> Self {
+        Self::parse(DEFAULT_LAYOUT).expect("default layout is valid")
+    }
+}
+
+impl fmt::Display for Layout {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.template)
+    }
+}
+
+fn parse_field(name: &str) -> Option<Field> {
+    match name.trim().to_ascii_lowercase().as_str() {
+        "category" => Some(Field::Category),
+        "name" => Some(Field::Name),
+        "stem" => Some(Field::Stem),
+        "ext" => Some(Field::Ext),
+        "year" => Some(Field::Year),
+        "month" => Some(Field::Month),
+        "day" => Some(Field::Day),
+        "parent" => Some(Field::Parent),
+        "dir" => Some(Field::Dir),
+        "size" => Some(Field::Size),
+        _ => None,
+    }
+}
+
+fn render_field(
+    field: Field,
+    file: &Path,
+    category: &Category,
+    date: Option<(i64, u32, u32)>,
+    source_dir: Option<&Path>,
+) -> String {
+    let os_part = |part: Option<&std::ffi::OsStr>, fallback: &str| {
+        part.map(|s| sanitize(&s.to_string_lossy()))
+            .filter(|s| !s.is_empty())
+            .unwrap_or_else(|| fallback.to_string())
+    };
+
+    match field {
+        Field::Category => category.name().to_string(),
+        Field::Name => os_part(file.file_name(), "unnamed"),
+        Field::Stem => os_part(file.file_stem(), "unnamed"),
+        Field::Ext => os_part(file.extension(), "no-ext").to_ascii_lowercase(),
+        Field::Parent => os_part(file.parent().and_then(|p| p.file_name()), "root"),
+        Field::Dir => relative_dir(file, source_dir),
+        Field::Year => date
+            .map(|(y, _, _)| format!("{:04}", y))
+            .unwrap_or_default(),
+        Field::Month => date
+            .map(|(_, m, _)| format!("{:02}", m))
+            .unwrap_or_default(),
+        Field::Day => date
+            .map(|(_, _, d)| format!("{:02}", d))
+            .unwrap_or_default(),
+        Field::Size => size_bucket(fs::metadata(file).map(|m| m.len()).unwrap_or(0)).to_string(),
+    }
+}
+
+/// The folders between `source_dir` and `file`, e.g. `trip/day1`; empty for
+/// files directly in the source folder or outside it.
+fn relative_dir(file: &Path, source_dir: Option<&Path>) -> String {
+    let Some(relative) = source_dir.and_then(|base| file.parent()?.strip_prefix(base).ok()) else {
+        return String::new();
+    };
+    relative
+        .components()
+        .filter_map(|component| match component {
+            Component::Normal(name) => Some(sanitize(&name.to_string_lossy())),
+            _ => None,
+        })
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// Keeps rendered values from introducing extra path segments.
+fn sanitize(value: &str) -> String {
+    match value {
+        "." | ".." => "_".to_string(),
+        _ => value.replace(['/', '\\'], "_"),
+    }
+}
+
+/// Pushes each `/`-separated segment of `rendered` onto `base`. Segments that
+/// would point at the current or parent folder become `_`, so a rendered path
+/// never leaves `base`.
+fn join_segments(base: &Path, rendered: &str) -> PathBuf {
+    let mut path = base.to_path_buf();
+    for segment in rendered.split('/').filter(|s| !s.is_empty()) {
+        match segment {
+            "." | ".." => path.push("_"),
+            _ => path.push(segment),
+        }
+    }
+    path
+}
+
+fn size_bucket(len: u64) -> &'static str {
+    const KB: u64 = 1024;
+    const MB: u64 = 1024 * KB;
+    const GB: u64 = 1024 * MB;
+    if len < KB {
+        "tiny"
+    } else if len < MB {
+        "small"
+    } else if len < 100 * MB {
+        "medium"
+    } else if len < GB {
+        "large"
+    } else {
+        "huge"
+    }
+}
+
+/// UTC calendar date of the file's modification time.
+fn modified_date(file: &Path) -> (i64, u32, u32) {
+    let secs = fs::metadata(file)
+        .and_then(|m| m.modified())
+        .unwrap_or(SystemTime::now())
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or(0);
+    civil_from_days(secs.div_euclid(86_400))
+}
+
+/// Converts days since 1970-01-01 to a (year, month, day) proleptic Gregorian date.
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{civil_from_days, size_bucket, Layout};
+    use crate::rules::Category;
+    use std::path::{Path, PathBuf};
+
+    #[test]
+    fn civil_dates() {
+        assert_eq!(civil_from_days(0), (1970, 1, 1));
+        assert_eq!(civil_from_days(19_782), (2024, 2, 29));
+        assert_eq!(civil_from_days(-1), (1969, 12, 31));
+    }
+
+    #[test]
+    fn renders_extension_layout() {
+        let layout = Layout::parse("{category}/{ext}/{name}").unwrap();
+        assert_eq!(
+            layout.target(
+                Path::new("out"),
+                Path::new("in/Photo.JPG"),
+                &Category::new("Images")
+            ),
+            PathBuf::from("out/Images/jpg/Photo.JPG")
+        );
+    }
+
+    #[test]
+    fn category_roots() {
+        let images = Category::new("Images");
+        let root = |template: &str| {
+            Layout::parse(template)
+                .unwrap()
+                .category_root(Path::new("out"), &images)
+        };
+        assert_eq!(root("{category}/{name}"), Some(PathBuf::from("out/Images")));
+        assert_eq!(
+            root("Sorted/{category}/{year}/{name}"),
+            Some(PathBuf::from("out/Sorted/Images"))
+        );
+        assert_eq!(root("{year}/{category}/{name}"), None);
+        assert_eq!(root("{category}-{name}"), None);
+    }
+
+    #[test]
+    fn rejects_bad_layouts() {
+        assert!(Layout::parse("{category}/{nmae}").is_err());
+        assert!(Layout::parse("{category}").is_err());
+        assert!(Layout::parse("../{name}").is_err());
+        assert!(Layout::parse("/abs/{name}").is_err());
+    }
+
+    #[test]
+    fn dot_names_stay_inside_the_destination() {
+        let layout = Layout::parse("{stem}/{name}").unwrap();
+        let others = Category::new("Others");
+        assert_eq!(
+            layout.target(Path::new("out"), Path::new("in/..."), &others),
+            PathBuf::from("out/_/...")
+        );
+        let glued = Layout::parse("{stem}./{name}").unwrap();
+        assert_eq!(
+            glued.target(Path::new("out"), Path::new("in/..."), &others),
+            PathBuf::from("out/_./...")
+        );
+        assert_eq!(
+            Layout::parse("{category}/{name}").unwrap().target(
+                Path::new("out"),
+                Path::new("in/a"),
+                &Category::new("..")
+            ),
+            PathBuf::from("out/_/a")
+        );
+    }
+
+    #[test]
+    fn size_buckets() {
+        assert_eq!(size_bucket(10), "tiny");
+        assert_eq!(size_bucket(5 * 1024 * 1024), "medium");
+    }
+
+    #[test]
+    fn keeps_structure_below_the_source() {
+        let layout = Layout::default()
+            .keep_structure()
+            .with_source_dir(Path::new("in"));
+        assert_eq!(layout.to_string(), "{category}/{dir}/{name}");
+        let images = Category::new("Images");
+        let target = |file: &str| layout.target(Path::new("out"), Path::new(file), &images);
+        assert_eq!(
+            target("in/trip/day1/a.jpg"),
+            PathBuf::from("out/Images/trip/day1/a.jpg")
+        );
+        assert_eq!(target("in/a.jpg"), PathBuf::from("out/Images/a.jpg"));
+
+        let dated = Layout::parse("{category}/{year}/IMG-{name}")
+            .unwrap()
+            .keep_structure();
+        assert_eq!(dated.to_string(), "{category}/{year}/{dir}/IMG-{name}");
+    }
+}
@@ -0,0 +1,59 @@
+//! Organize files into category folders by extension.
+//!
+//! The `rusty-sort` binary is a thin command-line wrapper around this crate.
+//! A typical run scans a folder, plans a move for every file and applies the
+//! plan:
+//!
+//! ```no_run
+//! use std::path::Path;
+//!
+//! use rusty_sort::journal::Journal;
+//! use rusty_sort::layout::Layout;
+//! use rusty_sort::organizer::{self, ApplyOptions};
+//! use rusty_sort::rules::Rules;
+//! use rusty_sort::transfer::Mode;
+//!
+//! # fn main() -> std::io::Result<()> {
+//! let src = Path::new("Downloads");
+//! let rules = Rules::default();
+//! let files = organizer::gather_files(src, &Default::default(), rules.filter())?;
+//! let plans = organizer::plan_moves(src, &files, &rules, &Layout::default());
+//!
+//! let mut journal = Journal::new(src);
+//! let options = ApplyOptions {
+//!     mode: Mode::Copy,
+//!     ..ApplyOptions::default()
+//! };
+//! let result = organizer::apply_moves(&plans, &rules, options, &mut journal)?;
+//! println!(
+//!     "moved {}, skipped {}, failed {}",
+//!     result.moved,
+//!     result.skipped,
+//!     result.errors.len()
+//! );
+//! # Ok(())
+//! # }
+//! ```
+
+#![warn(missing_docs)]
+
+mod condition;
+pub mod conflict;
+pub mod dedupe;
+pub mod filter;
+pub mod hidden;
+pub mod journal;
+pub mod layout;
+pub mod organizer;
+pub mod prune;
+pub mod report;
+pub mod rules;
+pub mod settings;
+mod sniff;
+pub mod state;
+mod toml;
+pub mod transfer;
+pub mod watch;
+
+pub use organizer::{apply_moves, plan_moves, ApplyOptions, MoveError, MovePlan, MoveResult};
+pub use rules::{Category, Rules};
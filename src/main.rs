@@ -1,343 +1,1421 @@
-use std::collections::HashSet;
 use std::env;
 use std::io;
-use std::io::Write;
+use std::io::{IsTerminal, Write};
 use std::path::{Path, PathBuf};
 
-mod organizer;
-mod rules;
+use rusty_sort::{
+    conflict, dedupe, filter, hidden, journal, layout, organizer, prune, report, rules, settings,
+    state, transfer, watch,
+};
+
+const USAGE: &str = "\
+Usage: rusty-sort <source> [options]
+       rusty-sort watch <source> [options]
+       rusty-sort dedupe <source> [options] [--keep <which>] [--report]
+       rusty-sort undo <source> [run-id]
+       rusty-sort config check <file>
+
+Options:
+  --to <dest>             Write category folders to <dest> instead of <source>
+  --config <file>         Load rules from <file> (.toml or Category=ext lines)
+  --strict                Fail if <file> maps an extension to several categories
+  --layout <template>     Destination template, default {category}/{name}
+  --keep-structure        Keep subfolders: <dest>/<Category>/<subfolders>/<file>
+  --exclude <glob>        Skip matching files (repeatable)
+  --include <glob>        Only sort matching files (repeatable)
+  --on-conflict <policy>  skip, rename, overwrite-if-newer, overwrite or dedupe
+  --mode <mode>           move (default), copy, or link (hard link; symlink
+                          across filesystems) files into place
+  --sniff                 Detect file types from their content
+  -r, --recursive         Scan subfolders
+  --max-depth <n>         Scan at most <n> folder levels deep
+  --symlinks <policy>     ignore (default), move-link, move-target or follow-dirs
+  --hidden <policy>       skip (default), include, or category (sort into Hidden)
+  --junk <policy>         skip (default), sort, delete or quarantine OS junk
+                          files such as .DS_Store and Thumbs.db
+  --resort                Re-sort files already in category folders
+  --empty-dirs <policy>   After a recursive sort, remove folders it emptied
+                          (emptied, default), keep them, or remove all empty
+                          folders (all)
+  -n, --dry-run           Preview the plan and ask before moving
+  --preview               Preview the plan and exit without asking
+  -y, --yes               Apply a --dry-run plan without asking
+  --fail-fast             Stop at the first file that cannot be moved
+  --format <format>       text (default), json, or ndjson
+  --keep <which>          dedupe: keep the oldest (default), newest or shortest name
+  --report                dedupe: only list duplicates, never move them
+
+Exit codes: 0 files moved, 1 error, 2 nothing to do, 3 some files skipped,
+            4 some files could not be moved";
+
+/// Process exit status, so scripts can tell runs apart.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Outcome {
+    Moved,
+    NothingToDo,
+    Skipped,
+    Failed,
+}
+
+const EXIT_ERROR: i32 = 1;
+
+impl Outcome {
+    fn from_result(result: &organizer::MoveResult) -> Self {
+        if !result.errors.is_empty() {
+            Outcome::Failed
+        } else if result.skipped > 0 {
+            Outcome::Skipped
+        } else if result.moved > 0
+            || result.deduped > 0
+            || result.junk_deleted > 0
+            || !result.removed_dirs.is_empty()
+        {
+            Outcome::Moved
+        } else {
+            Outcome::NothingToDo
+        }
+    }
+
+    /// A preview with pending work reports the "moved" code, so scripts can
+    /// tell "would move files" from "nothing to do".
+    fn for_preview(pending: bool) -> Self {
+        if pending {
+            Outcome::Moved
+        } else {
+            Outcome::NothingToDo
+        }
+    }
+
+    fn exit_code(self) -> i32 {
+        match self {
+            Outcome::Moved => 0,
+            Outcome::NothingToDo => 2,
+            Outcome::Skipped => 3,
+            Outcome::Failed => 4,
+        }
+    }
+}
+
+enum Command {
+    Sort(Config),
+    Watch(Config),
+    Dedupe(Config),
+    Undo {
+        src: PathBuf,
+        run_id: Option<String>,
+    },
+    CheckConfig(PathBuf),
+}
 
 struct Config {
     src: PathBuf,
     dest: PathBuf,
     dry_run: bool,
+    preview: bool,
+    yes: bool,
     recursive: bool,
+    max_depth: Option<usize>,
+    symlinks: organizer::SymlinkPolicy,
+    hidden: hidden::HiddenPolicy,
+    junk: hidden::JunkPolicy,
+    empty_dirs: prune::EmptyDirs,
+    resort: bool,
+    sniff: bool,
     config_path: Option<PathBuf>,
+    settings: Option<settings::Settings>,
+    strict: bool,
+    on_conflict: conflict::ConflictPolicy,
+    mode: transfer::Mode,
+    layout: layout::Layout,
+    excludes: Vec<filter::Glob>,
+    includes: Vec<filter::Glob>,
+    fail_fast: bool,
+    format: Format,
+    keep: dedupe::Keep,
+    report_only: bool,
 }
 
 fn main() {
-    if let Err(err) = run() {
-        eprintln!("Error: {}", err);
-        std::process::exit(1);
+    match run() {
+        Ok(outcome) => std::process::exit(outcome.exit_code()),
+        Err(err) => {
+            eprintln!("Error: {}", err);
+            std::process::exit(EXIT_ERROR);
+        }
     }
 }
 
-fn run() -> io::Result<()> {
-    let config = parse_args()?;
-    validate_directory(&config.src)?;
-    ensure_destination(&config.dest)?;
+fn run() -> io::Result<Outcome> {
+    match parse_args()? {
+        Command::Sort(config) => run_sort(config),
+        Command::Watch(config) => run_watch(config),
+        Command::Dedupe(config) => run_dedupe(config),
+        Command::Undo { src, run_id } => run_undo(&src, run_id.as_deref()),
+        Command::CheckConfig(path) => run_config_check(&path),
+    }
+}
 
+fn run_sort(config: Config) -> io::Result<Outcome> {
+    validate_directory(&config.src)?;
     let rules = load_rules(&config)?;
+    ensure_destination(&config.dest, &rules)?;
+    let mut reporter = Reporter::new(&config);
 
-    let files = gather_files(&config)?;
-    if files.is_empty() {
-        println!("No files found.");
-        return Ok(());
+    let scan = scan_files(&config, &rules)?;
+    if scan.files.is_empty() && !has_junk_to_handle(&config, &scan) {
+        if scan.ignored_links.is_empty() {
+            reporter.note("No files found.");
+        } else {
+            reporter.note(&format!(
+                "No files found ({} symlink(s) ignored, see --symlinks).",
+                scan.ignored_links.len()
+            ));
+        }
+        reporter.finish(false);
+        return Ok(Outcome::NothingToDo);
     }
 
-    print_banner("Rusty Sort");
-    println!("Read:  {}", config.src.display());
-    println!("Write: {}", config.dest.display());
+    reporter.start("Rusty Sort");
 
-    let previous_state = load_previous_state(&config.src)?;
+    let previous_state = state::load(&config.src)?;
     if !previous_state.is_empty() {
-        let (added, removed) = diff_state(&previous_state, &files, &config.src);
-        print_section("Change Summary");
-        println!("Added:   +{}", added);
-        println!("Removed: -{}", removed);
+        let (added, removed) = state::diff(&previous_state, &scan.files, &config.src);
+        reporter.changes(added, removed);
     }
 
-    let mut plans = organizer::plan_moves(&config.dest, &files, &rules);
+    let mut plans = plan_sort(&config, &rules, &scan);
 
-    let scan_counts = count_files_by_category(&files, &rules);
-    print_scan_summary(&scan_counts, files.len(), plans.len());
-    print_plan("Plan", &plans);
-    print_plan_summary(&plans);
+    let scan_counts = organizer::count_by_category(&scan.files, &rules);
+    reporter.scan(&scan_counts, &scan, plans.len(), config.junk);
+    reporter.plan("Plan", &plans, &rules);
+    let mut junk = junk_to_delete(&config, &scan).to_vec();
+    let mut pruner = empty_dir_pruner(&config, &rules, &scan);
+    let mut empty_dirs = pruner
+        .as_ref()
+        .map_or_else(Vec::new, |p| p.plan(&plans, config.on_conflict, &junk));
+    reporter.empty_dirs("Plan", &empty_dirs);
 
-    if config.dry_run {
-        print_section("Dry Run");
-        println!("Preview complete.");
-        if !prompt_yes_no("Proceed with these moves? (y/n): ")? {
-            println!("No changes made.");
-            return Ok(());
+    if config.preview || config.dry_run {
+        reporter.section("Dry Run");
+        reporter.note("Preview complete.");
+        // Prompts would block scripts and corrupt machine-readable output.
+        let interactive = config.format == Format::Text && io::stdin().is_terminal();
+        if config.preview || (!config.yes && !interactive) {
+            reporter.note("No changes made.");
+            reporter.finish(false);
+            let pending = !plans.is_empty() || !junk.is_empty() || !empty_dirs.is_empty();
+            return Ok(Outcome::for_preview(pending));
+        }
+        if !config.yes && !prompt_yes_no("Proceed with these moves? (y/n): ")? {
+            reporter.note("No changes made.");
+            reporter.finish(false);
+            return Ok(Outcome::NothingToDo);
         }
 
-        let latest_files = gather_files(&config)?;
-        let (added, removed) = diff_files(&files, &latest_files);
+        let latest = scan_files(&config, &rules)?;
+        let (added, removed) = state::diff_files(&scan.files, &latest.files);
         if added > 0 || removed > 0 {
-            println!(
+            reporter.note(&format!(
                 "Changes since preview: +{} new, -{} removed.",
                 added, removed
-            );
+            ));
         }
 
-        plans = organizer::plan_moves(&config.dest, &latest_files, &rules);
+        plans = plan_sort(&config, &rules, &latest);
+        junk = junk_to_delete(&config, &latest).to_vec();
+        pruner = empty_dir_pruner(&config, &rules, &latest);
         if added > 0 || removed > 0 {
-            let latest_counts = count_files_by_category(&latest_files, &rules);
-            print_scan_summary(&latest_counts, latest_files.len(), plans.len());
-            print_plan("Updated Plan", &plans);
-            print_plan_summary(&plans);
+            let latest_counts = organizer::count_by_category(&latest.files, &rules);
+            reporter.scan(&latest_counts, &latest, plans.len(), config.junk);
+            reporter.plan("Updated Plan", &plans, &rules);
+            empty_dirs = pruner
+                .as_ref()
+                .map_or_else(Vec::new, |p| p.plan(&plans, config.on_conflict, &junk));
+            reporter.empty_dirs("Updated Plan", &empty_dirs);
         }
     }
 
-    let result = organizer::apply_moves(&plans)?;
-    print_section("Result");
-    println!("Moved:   {}", result.moved);
-    println!("Skipped: {}", result.skipped);
-    if result.moved > 0 {
-        print_section("Moved By Category");
-        print_category_counts(&result.moved_by_category);
+    let mut journal = journal::Journal::new(&config.src);
+    let mut result = organizer::apply_moves(&plans, &rules, apply_options(&config), &mut journal)?;
+    let (deleted, errors) = hidden::delete_junk(&junk, config.fail_fast)?;
+    result.junk_deleted = deleted;
+    result.errors.extend(errors);
+    if let Some(pruner) = pruner {
+        let (removed, errors) = pruner.prune();
+        result.removed_dirs = removed;
+        result.errors.extend(errors);
     }
-    if result.skipped > 0 {
-        print_section("Skipped By Category");
-        print_category_counts(&result.skipped_by_category);
+    reporter.result(&result, journal.run_id());
+    reporter.finish(true);
+
+    let final_files = gather_files(&config, &rules)?;
+    state::save(&config.src, &final_files)?;
+
+    Ok(Outcome::from_result(&result))
+}
+
+/// Moves for the scanned files, plus quarantining junk if asked to.
+fn plan_sort(
+    config: &Config,
+    rules: &rules::Rules,
+    scan: &organizer::Scan,
+) -> Vec<organizer::MovePlan> {
+    let mut plans = organizer::plan_moves(&config.dest, &scan.files, rules, &config.layout);
+    if config.junk == hidden::JunkPolicy::Quarantine {
+        plans.extend(organizer::plan_into_folder(
+            &config.dest,
+            hidden::QUARANTINE_DIR,
+            &scan.junk,
+        ));
     }
+    plans
+}
 
-    let final_files = gather_files(&config)?;
-    save_state(&config.src, &final_files)?;
+/// Removes emptied folders after recursive moves; copies and links leave
+/// nothing empty behind.
+fn empty_dir_pruner(
+    config: &Config,
+    rules: &rules::Rules,
+    scan: &organizer::Scan,
+) -> Option<prune::Pruner> {
+    let applies = config.recursive
+        && config.mode == transfer::Mode::Move
+        && config.empty_dirs != prune::EmptyDirs::Keep;
+    if !applies {
+        return None;
+    }
+    Some(prune::Pruner::for_destination(
+        config.empty_dirs,
+        &scan.dirs,
+        &config.dest,
+        rules,
+        &config.layout,
+    ))
+}
 
-    Ok(())
+fn apply_options(config: &Config) -> organizer::ApplyOptions {
+    organizer::ApplyOptions {
+        mode: config.mode,
+        on_conflict: config.on_conflict,
+        fail_fast: config.fail_fast,
+    }
+}
+
+fn junk_to_delete<'a>(config: &Config, scan: &'a organizer::Scan) -> &'a [PathBuf] {
+    if config.junk == hidden::JunkPolicy::Delete {
+        &scan.junk
+    } else {
+        &[]
+    }
+}
+
+fn has_junk_to_handle(config: &Config, scan: &organizer::Scan) -> bool {
+    !scan.junk.is_empty()
+        && matches!(
+            config.junk,
+            hidden::JunkPolicy::Delete | hidden::JunkPolicy::Quarantine
+        )
+}
+
+/// Output format selected with `--format`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Format {
+    Text,
+    Json,
+    Ndjson,
+}
+
+impl Format {
+    fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "text" => Some(Format::Text),
+            "json" => Some(Format::Json),
+            "ndjson" => Some(Format::Ndjson),
+            _ => None,
+        }
+    }
+}
+
+/// Emits a run as human-readable text, one JSON document printed at the end,
+/// or one JSON object per line as events happen.
+struct Reporter {
+    format: Format,
+    on_conflict: conflict::ConflictPolicy,
+    mode: transfer::Mode,
+    src: PathBuf,
+    dest: PathBuf,
+    document: report::JsonObject,
+    changes: Option<report::JsonObject>,
+    scan: Option<report::JsonObject>,
+    plan: Option<String>,
+    empty_dirs: Option<String>,
+    result: Option<report::JsonObject>,
+}
+
+impl Reporter {
+    /// The JSON document starts with the run's folders and mode, so runs that
+    /// stop early still have the same fields.
+    fn new(config: &Config) -> Self {
+        Self {
+            format: config.format,
+            on_conflict: config.on_conflict,
+            mode: config.mode,
+            src: config.src.clone(),
+            dest: config.dest.clone(),
+            document: Self::header(&config.src, &config.dest, config.mode),
+            changes: None,
+            scan: None,
+            plan: None,
+            empty_dirs: None,
+            result: None,
+        }
+    }
+
+    fn header(src: &Path, dest: &Path, mode: transfer::Mode) -> report::JsonObject {
+        report::JsonObject::new()
+            .path("source", src)
+            .path("destination", dest)
+            .str("mode", &mode.to_string())
+    }
+
+    fn start(&mut self, title: &str) {
+        match self.format {
+            Format::Text => {
+                print_banner(title);
+                println!("Read:  {}", self.src.display());
+                println!("Write: {}", self.dest.display());
+                if self.mode != transfer::Mode::Move {
+                    println!("Mode:  {}", self.mode);
+                }
+            }
+            Format::Json => {}
+            Format::Ndjson => self.emit("start", Self::header(&self.src, &self.dest, self.mode)),
+        }
+    }
+
+    fn section(&self, title: &str) {
+        if self.format == Format::Text {
+            print_section(title);
+        }
+    }
+
+    /// Free-form progress messages; only shown as text.
+    fn note(&self, message: &str) {
+        if self.format == Format::Text {
+            println!("{}", message);
+        }
+    }
+
+    fn changes(&mut self, added: usize, removed: usize) {
+        let json = report::JsonObject::new()
+            .num("added", added)
+            .num("removed", removed);
+        match self.format {
+            Format::Text => {
+                print_section("Change Summary");
+                println!("Added:   +{}", added);
+                println!("Removed: -{}", removed);
+            }
+            Format::Json => self.changes = Some(json),
+            Format::Ndjson => self.emit("changes", json),
+        }
+    }
+
+    fn scan(
+        &mut self,
+        counts: &organizer::CategoryCounts,
+        scan: &organizer::Scan,
+        to_move: usize,
+        junk: hidden::JunkPolicy,
+    ) {
+        let total = scan.files.len();
+        let paths = |paths: &[PathBuf]| {
+            report::json_array(
+                paths
+                    .iter()
+                    .map(|path| report::json_string(&path.to_string_lossy())),
+            )
+        };
+        let json = report::JsonObject::new()
+            .num("total", total)
+            .num("already_sorted", total.saturating_sub(to_move))
+            .num("to_move", to_move)
+            .object("categories", report::counts_json(counts))
+            .raw("ignored_links", paths(&scan.ignored_links))
+            .num("hidden_skipped", scan.hidden)
+            .raw("junk", paths(&scan.junk))
+            .str("junk_policy", &junk.to_string());
+        match self.format {
+            Format::Text => print_scan_summary(counts, scan, to_move, junk),
+            Format::Json => self.scan = Some(json),
+            Format::Ndjson => self.emit("scan", json),
+        }
+    }
+
+    fn plan(&mut self, title: &str, plans: &[organizer::MovePlan], rules: &rules::Rules) {
+        match self.format {
+            Format::Text => {
+                print_plan(title, plans, self.on_conflict);
+                print_plan_summary(plans, rules);
+            }
+            Format::Json => {
+                let claimed = organizer::claimed_targets(plans);
+                self.plan = Some(report::json_array(plans.iter().zip(claimed).map(
+                    |(plan, claimed)| report::plan_json(plan, self.on_conflict, claimed).build(),
+                )));
+            }
+            Format::Ndjson => {
+                let claimed = organizer::claimed_targets(plans);
+                for (plan, claimed) in plans.iter().zip(claimed) {
+                    let stage = title.to_ascii_lowercase().replace(' ', "-");
+                    let json =
+                        report::plan_json(plan, self.on_conflict, claimed).str("stage", &stage);
+                    self.emit("plan", json);
+                }
+            }
+        }
+    }
+
+    /// Folders the plan will leave empty and remove.
+    fn empty_dirs(&mut self, title: &str, dirs: &[PathBuf]) {
+        if dirs.is_empty() {
+            return;
+        }
+        match self.format {
+            Format::Text => {
+                print_section("Empty Folders To Remove");
+                for dir in dirs {
+                    println!("{}", dir.display());
+                }
+            }
+            Format::Json => {
+                self.empty_dirs = Some(report::json_array(
+                    dirs.iter()
+                        .map(|dir| report::json_string(&dir.to_string_lossy())),
+                ));
+            }
+            Format::Ndjson => {
+                let stage = title.to_ascii_lowercase().replace(' ', "-");
+                for dir in dirs {
+                    let json = report::JsonObject::new()
+                        .path("path", dir)
+                        .str("stage", &stage);
+                    self.emit("remove-dir", json);
+                }
+            }
+        }
+    }
+
+    fn result(&mut self, result: &organizer::MoveResult, run_id: &str) {
+        let run_id = (result.moved > 0).then_some(run_id);
+        match self.format {
+            Format::Text => print_result(result, run_id),
+            Format::Json => self.result = Some(report::result_json(result, run_id)),
+            Format::Ndjson => self.emit("result", report::result_json(result, run_id)),
+        }
+    }
+
+    /// Prints the JSON document; text and NDJSON output is already written.
+    fn finish(self, applied: bool) {
+        if self.format != Format::Json {
+            return;
+        }
+        let optional = |obj: Option<report::JsonObject>| {
+            obj.map(|o| o.build()).unwrap_or_else(|| "null".to_string())
+        };
+        let document = self
+            .document
+            .bool("applied", applied)
+            .raw("changes", optional(self.changes))
+            .raw("scan", optional(self.scan))
+            .raw("plan", self.plan.unwrap_or_else(|| "[]".to_string()))
+            .raw(
+                "empty_dirs",
+                self.empty_dirs.unwrap_or_else(|| "[]".to_string()),
+            )
+            .raw("result", optional(self.result));
+        println!("{}", document.build());
+    }
+
+    fn emit(&self, event: &str, json: report::JsonObject) {
+        let line = report::JsonObject::new()
+            .str("event", event)
+            .raw("data", json.build());
+        println!("{}", line.build());
+    }
 }
 
-fn parse_args() -> io::Result<Config> {
+fn parse_args() -> io::Result<Command> {
+    let mut args = env::args().skip(1).peekable();
+    if args.peek().map(String::as_str) == Some("undo") {
+        args.next();
+        let Some(src) = args.next() else {
+            return Err(usage_error());
+        };
+        let run_id = args.next();
+        if args.next().is_some() {
+            return Err(usage_error());
+        }
+        return Ok(Command::Undo {
+            src: PathBuf::from(src),
+            run_id,
+        });
+    }
+    if args.peek().map(String::as_str) == Some("config") {
+        args.next();
+        let (Some(action), Some(path), None) = (args.next(), args.next(), args.next()) else {
+            return Err(usage_error());
+        };
+        if action != "check" {
+            return Err(usage_error());
+        }
+        return Ok(Command::CheckConfig(PathBuf::from(path)));
+    }
+    let watch = args.peek().map(String::as_str) == Some("watch");
+    let dedupe = args.peek().map(String::as_str) == Some("dedupe");
+    if watch || dedupe {
+        args.next();
+    }
+
     let mut dry_run = false;
+    let mut preview = false;
+    let mut yes = false;
     let mut recursive = false;
+    let mut max_depth: Option<usize> = None;
+    let mut symlinks = organizer::SymlinkPolicy::default();
+    let mut hidden = hidden::HiddenPolicy::default();
+    let mut junk = hidden::JunkPolicy::default();
+    let mut empty_dirs = prune::EmptyDirs::default();
+    let mut resort = false;
+    let mut sniff = false;
     let mut src: Option<PathBuf> = None;
     let mut dest: Option<PathBuf> = None;
     let mut config_path: Option<PathBuf> = None;
+    let mut on_conflict: Option<conflict::ConflictPolicy> = None;
+    let mut mode: Option<transfer::Mode> = None;
+    let mut layout: Option<layout::Layout> = None;
+    let mut keep_structure = false;
+    let mut excludes = Vec::new();
+    let mut includes = Vec::new();
+    let mut fail_fast = false;
+    let mut strict = false;
+    let mut keep: Option<dedupe::Keep> = None;
+    let mut report_only = false;
+    let mut format = Format::Text;
 
-    let mut args = env::args().skip(1).peekable();
     while let Some(arg) = args.next() {
         if arg == "--dry-run" || arg == "-n" {
             dry_run = true;
+        } else if arg == "--preview" {
+            preview = true;
+        } else if arg == "--yes" || arg == "-y" {
+            yes = true;
         } else if arg == "--recursive" || arg == "-r" {
             recursive = true;
-        } else if arg == "--config" {
+        } else if arg == "--max-depth" {
             let Some(value) = args.next() else {
-                return Err(io::Error::new(
+                return Err(usage_error());
+            };
+            let depth = value.parse().map_err(|_| {
+                io::Error::new(
                     io::ErrorKind::InvalidInput,
-                    "Usage: rusty-sort <source> [--to <dest>] [--config <file>] [--dry-run] [--recursive]",
-                ));
+                    format!("Invalid --max-depth: {}", value),
+                )
+            })?;
+            max_depth = Some(depth);
+            recursive = true;
+        } else if arg == "--symlinks" {
+            let Some(value) = args.next() else {
+                return Err(usage_error());
+            };
+            symlinks = organizer::SymlinkPolicy::parse(&value).ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!(
+                        "Unknown symlink policy: {} (expected ignore, move-link, move-target or follow-dirs)",
+                        value
+                    ),
+                )
+            })?;
+        } else if arg == "--hidden" {
+            let Some(value) = args.next() else {
+                return Err(usage_error());
+            };
+            hidden = hidden::HiddenPolicy::parse(&value).ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!(
+                        "Unknown hidden-file policy: {} (expected skip, include or category)",
+                        value
+                    ),
+                )
+            })?;
+        } else if arg == "--junk" {
+            let Some(value) = args.next() else {
+                return Err(usage_error());
+            };
+            junk = hidden::JunkPolicy::parse(&value).ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!(
+                        "Unknown junk policy: {} (expected skip, sort, delete or quarantine)",
+                        value
+                    ),
+                )
+            })?;
+        } else if arg == "--empty-dirs" {
+            let Some(value) = args.next() else {
+                return Err(usage_error());
+            };
+            empty_dirs = prune::EmptyDirs::parse(&value).ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!(
+                        "Unknown --empty-dirs: {} (expected emptied, keep or all)",
+                        value
+                    ),
+                )
+            })?;
+        } else if arg == "--resort" {
+            resort = true;
+        } else if arg == "--sniff" {
+            sniff = true;
+        } else if arg == "--config" {
+            let Some(value) = args.next() else {
+                return Err(usage_error());
             };
             config_path = Some(PathBuf::from(value));
-        } else if arg == "--to" {
+        } else if arg == "--on-conflict" {
             let Some(value) = args.next() else {
-                return Err(io::Error::new(
+                return Err(usage_error());
+            };
+            let policy = conflict::ConflictPolicy::parse(&value).ok_or_else(|| {
+                io::Error::new(
                     io::ErrorKind::InvalidInput,
-                    "Usage: rusty-sort <source> [--to <dest>] [--config <file>] [--dry-run] [--recursive]",
-                ));
+                    format!(
+                        "Unknown conflict policy: {} (expected skip, rename, overwrite-if-newer, overwrite or dedupe)",
+                        value
+                    ),
+                )
+            })?;
+            on_conflict = Some(policy);
+        } else if arg == "--mode" {
+            let Some(value) = args.next() else {
+                return Err(usage_error());
+            };
+            mode = Some(transfer::Mode::parse(&value).ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("Unknown mode: {} (expected move, copy or link)", value),
+                )
+            })?);
+        } else if arg == "--layout" {
+            let Some(value) = args.next() else {
+                return Err(usage_error());
+            };
+            layout = Some(layout::Layout::parse(&value)?);
+        } else if arg == "--keep-structure" {
+            keep_structure = true;
+        } else if arg == "--exclude" || arg == "--include" {
+            let Some(value) = args.next() else {
+                return Err(usage_error());
+            };
+            let glob = filter::Glob::parse(&value)?;
+            if arg == "--exclude" {
+                excludes.push(glob);
+            } else {
+                includes.push(glob);
+            }
+        } else if arg == "--keep" {
+            let Some(value) = args.next() else {
+                return Err(usage_error());
+            };
+            keep = Some(dedupe::Keep::parse(&value).ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!(
+                        "Unknown --keep: {} (expected oldest, newest or shortest)",
+                        value
+                    ),
+                )
+            })?);
+        } else if arg == "--report" {
+            report_only = true;
+        } else if arg == "--strict" {
+            strict = true;
+        } else if arg == "--fail-fast" {
+            fail_fast = true;
+        } else if arg == "--format" {
+            let Some(value) = args.next() else {
+                return Err(usage_error());
+            };
+            format = Format::parse(&value).ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("Unknown format: {} (expected text, json or ndjson)", value),
+                )
+            })?;
+        } else if arg == "--to" {
+            let Some(value) = args.next() else {
+                return Err(usage_error());
             };
             dest = Some(PathBuf::from(value));
         } else if src.is_none() {
             src = Some(PathBuf::from(arg));
         } else {
-            return Err(io::Error::new(
-                io::ErrorKind::InvalidInput,
-                "Usage: rusty-sort <source> [--to <dest>] [--config <file>] [--dry-run] [--recursive]",
-            ));
+            return Err(usage_error());
         }
     }
 
     let Some(src) = src else {
-        return Err(io::Error::new(
-            io::ErrorKind::InvalidInput,
-            "Usage: rusty-sort <source> [--to <dest>] [--config <file>] [--dry-run] [--recursive]",
-        ));
+        return Err(usage_error());
     };
 
     let dest = dest.unwrap_or_else(|| src.clone());
 
-    Ok(Config {
+    // Command-line options win over the rules file.
+    let settings = config_path.as_deref().map(settings::load).transpose()?;
+    let on_conflict = on_conflict
+        .or_else(|| settings.as_ref().and_then(|s| s.on_conflict))
+        .unwrap_or_default();
+    let mut layout = layout
+        .or_else(|| settings.as_ref().and_then(|s| s.layout.clone()))
+        .unwrap_or_default();
+    if keep_structure {
+        layout = layout.keep_structure();
+    }
+    let layout = layout.with_source_dir(&src);
+
+    let config = Config {
         src,
         dest,
         dry_run,
+        preview,
+        yes,
         recursive,
+        max_depth,
+        symlinks,
+        hidden,
+        junk,
+        empty_dirs,
+        resort,
+        sniff,
         config_path,
-    })
+        settings,
+        strict,
+        on_conflict,
+        mode: mode.unwrap_or_default(),
+        layout,
+        excludes,
+        includes,
+        fail_fast,
+        format,
+        keep: keep.unwrap_or_default(),
+        report_only,
+    };
+    if !dedupe && (keep.is_some() || report_only) {
+        return Err(usage_error());
+    }
+    // Junk is only deleted or quarantined by a plain sort.
+    let handles_junk = matches!(
+        config.junk,
+        hidden::JunkPolicy::Delete | hidden::JunkPolicy::Quarantine
+    );
+    if (dedupe || watch) && handles_junk {
+        return Err(usage_error());
+    }
+    if dedupe {
+        if config.format == Format::Ndjson || mode.is_some() {
+            return Err(usage_error());
+        }
+        return Ok(Command::Dedupe(config));
+    }
+    if watch {
+        if config.dry_run || config.preview || config.format == Format::Json {
+            return Err(usage_error());
+        }
+        return Ok(Command::Watch(config));
+    }
+    Ok(Command::Sort(config))
 }
 
-fn prompt_yes_no(message: &str) -> io::Result<bool> {
-    let mut input = String::new();
+fn run_watch(config: Config) -> io::Result<Outcome> {
+    validate_directory(&config.src)?;
+    let rules = load_rules(&config)?;
+    ensure_destination(&config.dest, &rules)?;
+
+    let mut reporter = Reporter::new(&config);
+    reporter.start("Rusty Sort Watch");
+    reporter.note("Watching for new files (Ctrl+C to stop).");
+
+    let mut tracker = watch::Tracker::new(&gather_files(&config, &rules)?);
+    let mut waiter = watch::Waiter::new(&config.src, config.recursive);
 
     loop {
-        print!("{}", message);
-        io::stdout().flush()?;
-        input.clear();
-        io::stdin().read_line(&mut input)?;
+        waiter.wait(tracker.has_pending());
 
-        match input.trim().to_ascii_lowercase().as_str() {
-            "y" | "yes" => return Ok(true),
-            "n" | "no" => return Ok(false),
-            _ => println!("Please enter y or n."),
+        let files = gather_files(&config, &rules)?;
+        let ready = tracker.update(&files);
+        if ready.is_empty() {
+            continue;
         }
+
+        let plans = organizer::plan_moves(&config.dest, &ready, &rules, &config.layout);
+        tracker.mark_known(ready);
+        if plans.is_empty() {
+            continue;
+        }
+
+        let mut journal = journal::Journal::new(&config.src);
+        let result = organizer::apply_moves(&plans, &rules, apply_options(&config), &mut journal)?;
+        if config.format == Format::Text {
+            print_plan("New Files", &plans, config.on_conflict);
+            println!(
+                "{}: {}, Skipped: {} (run id {})",
+                result.mode.past_tense(),
+                result.moved,
+                result.skipped,
+                journal.run_id()
+            );
+            print_failures(&result.errors);
+        } else {
+            reporter.plan("New Files", &plans, &rules);
+            reporter.result(&result, journal.run_id());
+        }
+        tracker.mark_known(plans.into_iter().map(|plan| plan.target));
+
+        state::save(&config.src, &gather_files(&config, &rules)?)?;
+        waiter.watch_tree(&config.src, config.recursive);
     }
 }
 
-fn gather_files(config: &Config) -> io::Result<Vec<PathBuf>> {
-    if config.recursive {
-        organizer::list_files_recursive(&config.src)
-    } else {
-        organizer::list_files(&config.src)
+fn run_dedupe(config: Config) -> io::Result<Outcome> {
+    validate_directory(&config.src)?;
+    ensure_directory(&config.dest)?;
+
+    let text = config.format == Format::Text;
+    let rules = load_rules(&config)?;
+    let files = dedupe_files(&config, &rules)?;
+    if files.is_empty() {
+        if text {
+            println!("No files found.");
+        } else {
+            print_dedupe_json(&config, &files, &Default::default(), "[]", false, None);
+        }
+        return Ok(Outcome::NothingToDo);
+    }
+
+    let found = dedupe::find_duplicates(&files, config.keep);
+    let groups = &found.groups;
+    let plans = dedupe::plan_duplicates(&config.dest, groups);
+    // Rendered now, while `target_exists` still describes the plan.
+    let claimed = organizer::claimed_targets(&plans);
+    let plan_json = report::json_array(plans.iter().zip(claimed).map(|(plan, claimed)| {
+        report::plan_json(plan, conflict::ConflictPolicy::Rename, claimed).build()
+    }));
+    // Files that could not be read still make the run "partly failed".
+    let scan_outcome = |outcome: Outcome| {
+        if found.errors.is_empty() {
+            outcome
+        } else {
+            Outcome::Failed
+        }
+    };
+
+    if text {
+        print_dedupe_scan(&config, &files, &found, &plans);
     }
+    let finish_without_changes = |outcome: Outcome| {
+        if !text {
+            print_dedupe_json(&config, &files, &found, &plan_json, false, None);
+        }
+        Ok(scan_outcome(outcome))
+    };
+    if groups.is_empty() {
+        if text {
+            println!("No duplicates found.");
+        }
+        return finish_without_changes(Outcome::NothingToDo);
+    }
+
+    if config.report_only {
+        if text {
+            println!("\nReport only; no changes made.");
+        }
+        return finish_without_changes(Outcome::Moved);
+    }
+    if config.preview || config.dry_run {
+        if text {
+            print_section("Dry Run");
+            println!("Preview complete.");
+        }
+        let interactive = text && io::stdin().is_terminal();
+        if config.preview || (!config.yes && !interactive) {
+            if text {
+                println!("No changes made.");
+            }
+            return finish_without_changes(Outcome::for_preview(!plans.is_empty()));
+        }
+        if !config.yes && !prompt_yes_no("Move these duplicates? (y/n): ")? {
+            println!("No changes made.");
+            return Ok(scan_outcome(Outcome::NothingToDo));
+        }
+    }
+
+    let mut journal = journal::Journal::new(&config.src);
+    let options = organizer::ApplyOptions {
+        mode: transfer::Mode::Move,
+        on_conflict: conflict::ConflictPolicy::Rename,
+        fail_fast: config.fail_fast,
+    };
+    let mut result = organizer::apply_moves(&plans, &rules, options, &mut journal)?;
+    let run_id = (result.moved > 0).then_some(journal.run_id());
+    if text {
+        print_section("Result");
+        println!("Moved:   {}", result.moved);
+        if !result.errors.is_empty() {
+            println!("Failed:  {}", result.errors.len());
+        }
+        if let Some(run_id) = run_id {
+            println!("Run id:  {}", run_id);
+        }
+    }
+    result.errors.extend(found.errors.iter().cloned());
+    if text && !result.errors.is_empty() {
+        print_section("Failures");
+        print_failures(&result.errors);
+    } else if !text {
+        print_dedupe_json(
+            &config,
+            &files,
+            &found,
+            &plan_json,
+            true,
+            Some((&result, run_id)),
+        );
+    }
+
+    state::save(&config.src, &gather_files(&config, &rules)?)?;
+    Ok(Outcome::from_result(&result))
 }
 
-fn diff_files(before: &[PathBuf], after: &[PathBuf]) -> (usize, usize) {
-    let before_set: HashSet<&PathBuf> = before.iter().collect();
-    let after_set: HashSet<&PathBuf> = after.iter().collect();
+fn print_dedupe_scan(
+    config: &Config,
+    files: &[PathBuf],
+    found: &dedupe::Duplicates,
+    plans: &[organizer::MovePlan],
+) {
+    let groups = &found.groups;
+    print_banner("Rusty Sort Dedupe");
+    println!("Read:  {}", config.src.display());
+    println!(
+        "Write: {}",
+        config.dest.join(dedupe::DUPLICATES_DIR).display()
+    );
 
-    let added = after_set.difference(&before_set).count();
-    let removed = before_set.difference(&after_set).count();
+    let extras: usize = groups.iter().map(|group| group.extras().len()).sum();
+    print_section("Scan Summary");
+    println!("Files scanned:    {}", files.len());
+    println!("Duplicate groups: {}", groups.len());
+    println!("Duplicate files:  {}", extras);
+    println!("Reclaimable:      {} bytes", dedupe::reclaimable(groups));
+    if !found.errors.is_empty() {
+        println!("Unreadable:       {}", found.errors.len());
+        print_section("Unreadable Files");
+        print_failures(&found.errors);
+    }
+    if groups.is_empty() {
+        return;
+    }
 
-    (added, removed)
+    print_section(if config.report_only {
+        "Duplicates"
+    } else {
+        "Plan"
+    });
+    let mut targets = plans.iter();
+    for group in groups {
+        println!("Keep ({}): {}", config.keep, group.keep().display());
+        for extra in group.extras() {
+            match targets.next() {
+                Some(plan) if !config.report_only => {
+                    println!("  {} -> {}", extra.display(), plan.target.display())
+                }
+                _ => println!("  {}", extra.display()),
+            }
+        }
+    }
 }
 
-fn state_path(base_dir: &Path) -> PathBuf {
-    base_dir.join(".rusty-sort-state.txt")
+/// One JSON document for a dedupe run: the scan (including unreadable
+/// files), the duplicate groups, the plan and, once applied, the result.
+fn print_dedupe_json(
+    config: &Config,
+    files: &[PathBuf],
+    found: &dedupe::Duplicates,
+    plan: &str,
+    applied: bool,
+    result: Option<(&organizer::MoveResult, Option<&str>)>,
+) {
+    let groups = &found.groups;
+    let paths = |paths: &[PathBuf]| {
+        report::json_array(
+            paths
+                .iter()
+                .map(|path| report::json_string(&path.to_string_lossy())),
+        )
+    };
+    let scan = report::JsonObject::new()
+        .num("total", files.len())
+        .num("groups", groups.len())
+        .num(
+            "duplicates",
+            groups.iter().map(|group| group.extras().len()).sum(),
+        )
+        .raw("reclaimable_bytes", dedupe::reclaimable(groups).to_string())
+        .raw("errors", report::errors_json(&found.errors));
+    let groups_json = report::json_array(groups.iter().map(|group| {
+        report::JsonObject::new()
+            .path("keep", group.keep())
+            .raw("size", group.size.to_string())
+            .raw("extras", paths(group.extras()))
+            .build()
+    }));
+    let result = result.map_or_else(
+        || "null".to_string(),
+        |(result, run_id)| report::result_json(result, run_id).build(),
+    );
+    let document = report::JsonObject::new()
+        .path("source", &config.src)
+        .path("destination", &config.dest.join(dedupe::DUPLICATES_DIR))
+        .str("keep", &config.keep.to_string())
+        .bool("applied", applied)
+        .object("scan", scan)
+        .raw("groups", groups_json)
+        .raw("plan", plan.to_string())
+        .raw("result", result);
+    println!("{}", document.build());
 }
 
-fn load_previous_state(base_dir: &Path) -> io::Result<Vec<PathBuf>> {
-    let path = state_path(base_dir);
-    if !path.exists() {
-        return Ok(Vec::new());
+fn run_config_check(path: &Path) -> io::Result<Outcome> {
+    let diagnostics = settings::check(path)?;
+    if diagnostics.is_empty() {
+        println!("{}: OK", path.display());
+        return Ok(Outcome::Moved);
+    }
+    for diagnostic in &diagnostics {
+        println!("{}: {}", path.display(), diagnostic);
     }
+    Err(io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!(
+            "{} problem(s) found in {}",
+            diagnostics.len(),
+            path.display()
+        ),
+    ))
+}
 
-    let content = std::fs::read_to_string(path)?;
-    let mut files = Vec::new();
-    for line in content.lines() {
-        if line.trim().is_empty() {
-            continue;
-        }
-        files.push(base_dir.join(line));
+fn usage_error() -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, USAGE)
+}
+
+fn run_undo(src: &Path, run_id: Option<&str>) -> io::Result<Outcome> {
+    validate_directory(src)?;
+
+    let journal::Loaded { entries, invalid } = journal::load(src)?;
+    for line in &invalid {
+        eprintln!(
+            "Warning: skipping unreadable journal line {}: {}",
+            line.number, line.text
+        );
     }
-    Ok(files)
+    let Some(run_id) = run_id.or_else(|| journal::last_run_id(&entries)) else {
+        println!("Nothing to undo.");
+        return Ok(Outcome::NothingToDo);
+    };
+    let run_id = run_id.to_string();
+
+    let run_entries: Vec<&journal::Entry> = entries.iter().filter(|e| e.run_id == run_id).collect();
+    if run_entries.is_empty() {
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("No journal entries for run {}", run_id),
+        ));
+    }
+
+    print_banner("Rusty Sort Undo");
+    println!("Run: {}", run_id);
+    print_section("Undo");
+
+    let mut restored = Vec::new();
+    let mut refused = 0usize;
+    let mut failed = 0usize;
+    for entry in run_entries.iter().rev() {
+        // One entry that cannot be moved back must not stop the rest, or the
+        // journal would keep entries that were already restored.
+        let outcome = match journal::undo_entry(entry) {
+            Ok(outcome) => outcome,
+            Err(err) => {
+                failed += 1;
+                println!("Failed: {} ({})", entry.target.display(), err);
+                continue;
+            }
+        };
+        let note = match outcome {
+            journal::UndoOutcome::Restored => {
+                restored.push((*entry).clone());
+                println!("{} -> {}", entry.target.display(), entry.source.display());
+                continue;
+            }
+            journal::UndoOutcome::Removed => {
+                restored.push((*entry).clone());
+                println!("Removed {} ({})", entry.target.display(), entry.mode);
+                continue;
+            }
+            journal::UndoOutcome::TargetMissing => "target no longer exists",
+            journal::UndoOutcome::TargetChanged => "target changed since the move",
+            journal::UndoOutcome::SourceExists => "original path is occupied",
+        };
+        refused += 1;
+        println!("Refused: {} ({})", entry.target.display(), note);
+    }
+
+    let remaining: Vec<journal::Entry> = entries
+        .into_iter()
+        .filter(|e| !restored.contains(e))
+        .collect();
+    journal::save(src, &remaining, &invalid)?;
+
+    print_section("Result");
+    println!("Restored: {}", restored.len());
+    println!("Refused:  {}", refused);
+    if failed > 0 {
+        println!("Failed:   {}", failed);
+    }
+
+    Ok(if failed > 0 {
+        Outcome::Failed
+    } else if refused > 0 {
+        Outcome::Skipped
+    } else {
+        Outcome::Moved
+    })
 }
 
-fn save_state(base_dir: &Path, files: &[PathBuf]) -> io::Result<()> {
-    let mut lines = String::new();
-    for file in files {
-        if let Ok(rel) = file.strip_prefix(base_dir) {
-            lines.push_str(&rel.to_string_lossy());
-            lines.push('\n');
+fn prompt_yes_no(message: &str) -> io::Result<bool> {
+    let mut input = String::new();
+
+    loop {
+        print!("{}", message);
+        io::stdout().flush()?;
+        input.clear();
+        io::stdin().read_line(&mut input)?;
+
+        match input.trim().to_ascii_lowercase().as_str() {
+            "y" | "yes" => return Ok(true),
+            "n" | "no" => return Ok(false),
+            _ => println!("Please enter y or n."),
         }
     }
-    std::fs::write(state_path(base_dir), lines)
 }
 
-fn diff_state(previous: &[PathBuf], current: &[PathBuf], base_dir: &Path) -> (usize, usize) {
-    let prev_rel: HashSet<PathBuf> = previous
-        .iter()
-        .filter_map(|p| p.strip_prefix(base_dir).ok().map(|r| r.to_path_buf()))
-        .collect();
-    let curr_rel: HashSet<PathBuf> = current
-        .iter()
-        .filter_map(|p| p.strip_prefix(base_dir).ok().map(|r| r.to_path_buf()))
-        .collect();
+fn gather_files(config: &Config, rules: &rules::Rules) -> io::Result<Vec<PathBuf>> {
+    Ok(scan_files(config, rules)?.files)
+}
 
-    let added = curr_rel.difference(&prev_rel).count();
-    let removed = prev_rel.difference(&curr_rel).count();
+fn scan_files(config: &Config, rules: &rules::Rules) -> io::Result<organizer::Scan> {
+    let mut options = scan_options(config);
+    if !config.resort {
+        options.skip_output_dirs(&config.dest, rules, &config.layout);
+    }
+    organizer::scan_files(&config.src, &options, rules.filter())
+}
 
-    (added, removed)
+/// Sorted files are where duplicates pile up, so dedupe looks inside the
+/// category folders and only leaves out the folders it or `--junk` fill.
+fn dedupe_files(config: &Config, rules: &rules::Rules) -> io::Result<Vec<PathBuf>> {
+    let mut options = scan_options(config);
+    options.skip_set_aside_dirs(&config.dest);
+    organizer::gather_files(&config.src, &options, rules.filter())
 }
 
-fn print_plan_summary(plans: &[organizer::MovePlan]) {
-    let mut images = 0usize;
-    let mut documents = 0usize;
-    let mut videos = 0usize;
-    let mut audio = 0usize;
-    let mut archives = 0usize;
-    let mut others = 0usize;
+fn scan_options(config: &Config) -> organizer::ScanOptions {
+    organizer::ScanOptions {
+        recursive: config.recursive,
+        max_depth: config.max_depth,
+        skip_dirs: Vec::new(),
+        symlinks: config.symlinks,
+        hidden: config.hidden,
+        junk: config.junk,
+    }
+}
 
+fn print_plan_summary(plans: &[organizer::MovePlan], rules: &rules::Rules) {
+    let mut counts = organizer::CategoryCounts::for_rules(rules);
     for plan in plans {
-        match plan.category {
-            rules::Category::Images => images += 1,
-            rules::Category::Documents => documents += 1,
-            rules::Category::Videos => videos += 1,
-            rules::Category::Audio => audio += 1,
-            rules::Category::Archives => archives += 1,
-            rules::Category::Others => others += 1,
-        }
+        counts.inc(&plan.category);
     }
 
     let total = plans.len();
     print_section("Plan Summary");
-    println!("Images: {}", images);
-    println!("Documents: {}", documents);
-    println!("Videos: {}", videos);
-    println!("Audio: {}", audio);
-    println!("Archives: {}", archives);
-    println!("Others: {}", others);
+    print_category_counts(&counts);
     println!("Total: {}", total);
 }
 
-fn print_plan(title: &str, plans: &[organizer::MovePlan]) {
+/// Lists `plans`, grouped by destination root when there is more than one.
+fn print_plan(title: &str, plans: &[organizer::MovePlan], on_conflict: conflict::ConflictPolicy) {
     print_section(title);
+    let mut roots: Vec<&Path> = Vec::new();
     for plan in plans {
+        if !roots.contains(&plan.root.as_path()) {
+            roots.push(&plan.root);
+        }
+    }
+    let claimed = organizer::claimed_targets(plans);
+    let lines = plans.iter().zip(claimed);
+    if roots.len() < 2 {
+        print_plan_lines(lines, on_conflict, "");
+        return;
+    }
+    for root in roots {
+        println!("{}:", root.display());
+        let in_root = lines.clone().filter(|(plan, _)| plan.root == root);
+        print_plan_lines(in_root, on_conflict, "  ");
+    }
+}
+
+/// Prints each plan with a note when its target exists or is `claimed` by an
+/// earlier plan.
+fn print_plan_lines<'a>(
+    plans: impl Iterator<Item = (&'a organizer::MovePlan, bool)>,
+    on_conflict: conflict::ConflictPolicy,
+    indent: &str,
+) {
+    for (plan, claimed) in plans {
         let exists_note = if plan.target.exists() {
-            " (target exists)"
+            format!(" (target exists: {})", on_conflict)
+        } else if claimed {
+            format!(" (target claimed by an earlier file: {})", on_conflict)
         } else {
-            ""
+            String::new()
+        };
+        let mismatch_note = match plan.mismatch {
+            Some(detected) => format!(" (content is {}, not its extension)", detected),
+            None => String::new(),
         };
         println!(
-            "[{}] {} -> {}{}",
+            "{}[{}] {} -> {}{}{}",
+            indent,
             plan.category,
             plan.source.display(),
             plan.target.display(),
-            exists_note
+            exists_note,
+            mismatch_note
         );
     }
 }
 
-fn count_files_by_category(files: &[PathBuf], rules: &rules::Rules) -> CategoryCounts {
-    let mut counts = CategoryCounts::default();
-    for file in files {
-        counts.inc(rules.classify(file));
+fn print_result(result: &organizer::MoveResult, run_id: Option<&str>) {
+    print_section("Result");
+    println!(
+        "{:<8} {}",
+        format!("{}:", result.mode.past_tense()),
+        result.moved
+    );
+    println!("Skipped: {}", result.skipped);
+    if !result.errors.is_empty() {
+        println!("Failed:  {}", result.errors.len());
+    }
+    if result.renamed > 0 {
+        println!("Renamed: {}", result.renamed);
+    }
+    if result.overwritten > 0 {
+        println!("Overwritten: {}", result.overwritten);
+    }
+    if result.deduped > 0 && result.mode == transfer::Mode::Move {
+        println!("Duplicates removed: {}", result.deduped);
+    } else if result.deduped > 0 {
+        println!("Already in place: {}", result.deduped);
+    }
+    if result.copied > 0 {
+        println!("Copied across filesystems: {}", result.copied);
+    }
+    if result.symlinked > 0 {
+        println!("Symlinked across filesystems: {}", result.symlinked);
+    }
+    if result.junk_deleted > 0 {
+        println!("Junk deleted: {}", result.junk_deleted);
+    }
+    if !result.removed_dirs.is_empty() {
+        println!("Empty folders removed: {}", result.removed_dirs.len());
+    }
+    if let Some(run_id) = run_id {
+        println!("Run id:  {}", run_id);
+    }
+    if !result.journal_errors.is_empty() {
+        println!("Not journaled: {}", result.journal_errors.len());
+    }
+    if result.moved > 0 {
+        print_section(&format!("{} By Category", result.mode.past_tense()));
+        print_category_counts(&result.moved_by_category);
+    }
+    if result.skipped > 0 {
+        print_section("Skipped By Category");
+        print_category_counts(&result.skipped_by_category);
+    }
+    if !result.errors.is_empty() {
+        print_section("Failures");
+        print_failures(&result.errors);
+    }
+    if !result.journal_errors.is_empty() {
+        print_section("Not Journaled (undo cannot revert these)");
+        print_failures(&result.journal_errors);
     }
-    counts
 }
 
-fn print_scan_summary(counts: &CategoryCounts, total: usize, to_move: usize) {
+fn print_failures(errors: &[organizer::MoveError]) {
+    for error in errors {
+        println!("{}: {}", error.source.display(), error.reason);
+    }
+}
+
+fn print_scan_summary(
+    counts: &organizer::CategoryCounts,
+    scan: &organizer::Scan,
+    to_move: usize,
+    junk: hidden::JunkPolicy,
+) {
+    let total = scan.files.len();
     let already_sorted = total.saturating_sub(to_move);
     print_section("Scan Summary");
-    println!("Images: {}", counts.images);
-    println!("Documents: {}", counts.documents);
-    println!("Videos: {}", counts.videos);
-    println!("Audio: {}", counts.audio);
-    println!("Archives: {}", counts.archives);
-    println!("Others: {}", counts.others);
+    print_category_counts(counts);
     println!("Total files: {}", total);
     println!("Already sorted: {}", already_sorted);
     println!("To move: {}", to_move);
+    if !scan.ignored_links.is_empty() {
+        println!(
+            "Ignored symlinks: {} (see --symlinks)",
+            scan.ignored_links.len()
+        );
+        for link in &scan.ignored_links {
+            println!("  {}", link.display());
+        }
+    }
+    if scan.hidden > 0 {
+        println!("Hidden skipped: {} (see --hidden)", scan.hidden);
+    }
+    if !scan.junk.is_empty() {
+        println!("Junk files: {} ({}, see --junk)", scan.junk.len(), junk);
+        for file in &scan.junk {
+            println!("  {}", file.display());
+        }
+    }
 }
 
 fn print_category_counts(counts: &organizer::CategoryCounts) {
-    println!("Images: {}", counts.images);
-    println!("Documents: {}", counts.documents);
-    println!("Videos: {}", counts.videos);
-    println!("Audio: {}", counts.audio);
-    println!("Archives: {}", counts.archives);
-    println!("Others: {}", counts.others);
-}
-
-#[derive(Default)]
-struct CategoryCounts {
-    images: usize,
-    documents: usize,
-    videos: usize,
-    audio: usize,
-    archives: usize,
-    others: usize,
-}
-
-impl CategoryCounts {
-    fn inc(&mut self, category: rules::Category) {
-        match category {
-            rules::Category::Images => self.images += 1,
-            rules::Category::Documents => self.documents += 1,
-            rules::Category::Videos => self.videos += 1,
-            rules::Category::Audio => self.audio += 1,
-            rules::Category::Archives => self.archives += 1,
-            rules::Category::Others => self.others += 1,
-        }
+    for (category, count) in counts.iter() {
+        println!("{}: {}", category, count);
     }
 }
 
@@ -359,7 +1437,15 @@ fn validate_directory(path: &Path) -> io::Result<()> {
     Ok(())
 }
 
-fn ensure_destination(path: &Path) -> io::Result<()> {
+/// Creates the destination and every category's own destination root.
+fn ensure_destination(dest: &Path, rules: &rules::Rules) -> io::Result<()> {
+    for root in rules.destination_roots(dest) {
+        ensure_directory(&root)?;
+    }
+    Ok(())
+}
+
+fn ensure_directory(path: &Path) -> io::Result<()> {
     if !path.exists() {
         std::fs::create_dir_all(path)?;
         return Ok(());
@@ -367,17 +1453,41 @@ fn ensure_destination(path: &Path) -> io::Result<()> {
     if !path.is_dir() {
         return Err(io::Error::new(
             io::ErrorKind::InvalidInput,
-            "Destination path is not a directory",
+            format!("Destination path is not a directory: {}", path.display()),
         ));
     }
     Ok(())
 }
 
 fn load_rules(config: &Config) -> io::Result<rules::Rules> {
-    match &config.config_path {
-        Some(path) => rules::Rules::from_config(path),
-        None => Ok(rules::Rules::default()),
+    let mut rules = match (&config.settings, &config.config_path) {
+        (Some(settings), Some(path)) => {
+            for warning in settings.warnings.iter().chain(&settings.overlaps) {
+                eprintln!("Warning: {}: {}", path.display(), warning);
+            }
+            if config.strict && !settings.overlaps.is_empty() {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!(
+                        "{} overlapping extension(s) in {} (--strict)",
+                        settings.overlaps.len(),
+                        path.display()
+                    ),
+                ));
+            }
+            settings.rules.clone()
+        }
+        _ => rules::Rules::default(),
+    };
+    for glob in &config.excludes {
+        rules.filter_mut().exclude(glob.clone());
+    }
+    for glob in &config.includes {
+        rules.filter_mut().include(glob.clone());
     }
+    Ok(rules
+        .with_sniff(config.sniff)
+        .with_hidden_category(config.hidden == hidden::HiddenPolicy::Category))
 }
 fn print_banner(title: &str) {
     println!("== {} ==", title);
@@ -387,3 +1497,59 @@ fn print_section(title: &str) {
     println!();
     println!("-- {} --", title);
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{Outcome, EXIT_ERROR};
+    use rusty_sort::organizer::{MoveError, MoveResult};
+    use std::path::PathBuf;
+
+    #[test]
+    fn exit_codes_are_distinct() {
+        assert_eq!(Outcome::Moved.exit_code(), 0);
+        assert_eq!(EXIT_ERROR, 1);
+        assert_eq!(Outcome::NothingToDo.exit_code(), 2);
+        assert_eq!(Outcome::Skipped.exit_code(), 3);
+        assert_eq!(Outcome::Failed.exit_code(), 4);
+    }
+
+    #[test]
+    fn previews_report_pending_work() {
+        assert_eq!(Outcome::for_preview(true).exit_code(), 0);
+        assert_eq!(Outcome::for_preview(false).exit_code(), 2);
+    }
+
+    #[test]
+    fn outcome_from_result() {
+        let outcome = |edit: fn(&mut MoveResult)| {
+            let mut result = MoveResult::default();
+            edit(&mut result);
+            Outcome::from_result(&result)
+        };
+        assert_eq!(outcome(|_| {}), Outcome::NothingToDo);
+        assert_eq!(outcome(|r| r.moved = 1), Outcome::Moved);
+        assert_eq!(outcome(|r| r.deduped = 1), Outcome::Moved);
+        assert_eq!(outcome(|r| r.junk_deleted = 1), Outcome::Moved);
+        assert_eq!(
+            outcome(|r| r.removed_dirs.push(PathBuf::from("empty"))),
+            Outcome::Moved
+        );
+        assert_eq!(
+            outcome(|r| {
+                r.moved = 1;
+                r.skipped = 1;
+            }),
+            Outcome::Skipped
+        );
+        assert_eq!(
+            outcome(|r| {
+                r.skipped = 1;
+                r.errors.push(MoveError {
+                    source: PathBuf::from("a.txt"),
+                    reason: "denied".to_string(),
+                });
+            }),
+            Outcome::Failed
+        );
+    }
+}
@@ -1,102 +1,428 @@
+//! Scanning folders, planning moves and applying them.
+
+use std::collections::HashSet;
+use std::fmt;
 use std::fs;
 use std::io;
 use std::path::{Path, PathBuf};
 
+use crate::conflict::{self, ConflictPolicy, Resolution};
+use crate::dedupe::DUPLICATES_DIR;
+use crate::filter::Filter;
+use crate::hidden::{self, HiddenPolicy, JunkPolicy, QUARANTINE_DIR};
+use crate::journal::{Journal, JOURNAL_FILE_NAME};
+use crate::layout::Layout;
 use crate::rules::{Category, Rules};
+use crate::state::STATE_FILE_NAME;
+use crate::transfer::{self, Mode, Transfer};
 
-pub fn list_files(dir: &Path) -> io::Result<Vec<PathBuf>> {
-    let mut files = Vec::new();
+/// How `gather_files` walks the source folder.
+#[derive(Debug, Clone, Default)]
+pub struct ScanOptions {
+    /// Descend into subfolders.
+    pub recursive: bool,
+    /// Deepest folder level below the source to descend into; `None` for no limit.
+    pub max_depth: Option<usize>,
+    /// Folders that are never descended into.
+    pub skip_dirs: Vec<PathBuf>,
+    /// What to do with symbolic links.
+    pub symlinks: SymlinkPolicy,
+    /// What to do with hidden files and folders.
+    pub hidden: HiddenPolicy,
+    /// Junk files are set aside in `Scan::junk` unless this is `Sort`.
+    pub junk: JunkPolicy,
+}
 
-    for entry in fs::read_dir(dir)? {
-        let entry = entry?;
-        let file_type = entry.file_type()?;
-        if file_type.is_file() {
-            let path = entry.path();
-            if is_state_file(&path) {
+impl ScanOptions {
+    /// Skips the destination's existing category folders so already sorted
+    /// files are not shuffled again by a recursive scan.
+    pub fn skip_category_dirs(&mut self, dest_dir: &Path, rules: &Rules, layout: &Layout) {
+        for category in rules.categories() {
+            let dest_dir = rules.destination(dest_dir, &category);
+            let Some(root) = layout.category_root(&dest_dir, &category) else {
                 continue;
-            }
-            files.push(path);
+            };
+            self.skip_dir(&root);
+        }
+    }
+
+    /// Skips every folder a sort into `dest_dir` writes to: the category
+    /// folders, `Duplicates/` and `Quarantine/`.
+    pub fn skip_output_dirs(&mut self, dest_dir: &Path, rules: &Rules, layout: &Layout) {
+        self.skip_category_dirs(dest_dir, rules, layout);
+        self.skip_set_aside_dirs(dest_dir);
+    }
+
+    /// Skips `Duplicates/` and `Quarantine/`, where dedupe and junk
+    /// quarantining set files aside.
+    pub fn skip_set_aside_dirs(&mut self, dest_dir: &Path) {
+        self.skip_dir(&dest_dir.join(DUPLICATES_DIR));
+        self.skip_dir(&dest_dir.join(QUARANTINE_DIR));
+    }
+
+    /// Never descends into `dir`, if it exists.
+    pub fn skip_dir(&mut self, dir: &Path) {
+        if let Ok(dir) = fs::canonicalize(dir) {
+            self.skip_dirs.push(dir);
         }
     }
+}
 
-    Ok(files)
+/// What a scan does with symbolic links.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SymlinkPolicy {
+    /// Leave links alone and report them.
+    #[default]
+    Ignore,
+    /// Sort links to files like files; the link itself is moved.
+    MoveLink,
+    /// Sort the file a link points to; the link is left behind.
+    MoveTarget,
+    /// Descend into linked folders in recursive scans, each real folder once.
+    FollowDirs,
 }
 
-pub fn list_files_recursive(dir: &Path) -> io::Result<Vec<PathBuf>> {
-    let mut files = Vec::new();
-    collect_files_recursive(dir, &mut files)?;
-    Ok(files)
+impl SymlinkPolicy {
+    /// Parses `ignore`, `move-link`, `move-target` or `follow-dirs`.
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "ignore" => Some(SymlinkPolicy::Ignore),
+            "move-link" => Some(SymlinkPolicy::MoveLink),
+            "move-target" => Some(SymlinkPolicy::MoveTarget),
+            "follow-dirs" => Some(SymlinkPolicy::FollowDirs),
+            _ => None,
+        }
+    }
 }
 
-fn collect_files_recursive(dir: &Path, files: &mut Vec<PathBuf>) -> io::Result<()> {
-    for entry in fs::read_dir(dir)? {
-        let entry = entry?;
-        let file_type = entry.file_type()?;
-        let path = entry.path();
-        if file_type.is_file() {
-            if is_state_file(&path) {
-                continue;
+impl fmt::Display for SymlinkPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            SymlinkPolicy::Ignore => "ignore",
+            SymlinkPolicy::MoveLink => "move-link",
+            SymlinkPolicy::MoveTarget => "move-target",
+            SymlinkPolicy::FollowDirs => "follow-dirs",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// The files a scan found, plus the symbolic links it left alone.
+#[derive(Debug, Default)]
+pub struct Scan {
+    /// Files to sort.
+    pub files: Vec<PathBuf>,
+    /// Links skipped by the symlink policy, broken links, and linked folders
+    /// that would have been scanned twice or looped.
+    pub ignored_links: Vec<PathBuf>,
+    /// OS junk files such as `.DS_Store`; see [`crate::hidden`].
+    pub junk: Vec<PathBuf>,
+    /// Hidden files left alone by `HiddenPolicy::Skip`, plus hidden folders
+    /// not descended into.
+    pub hidden: usize,
+    /// Subfolders walked by a recursive scan, not counting linked ones.
+    pub dirs: Vec<PathBuf>,
+}
+
+/// Lists files in `dir` as described by `options`, skipping anything `filter`
+/// rejects.
+pub fn scan_files(dir: &Path, options: &ScanOptions, filter: &Filter) -> io::Result<Scan> {
+    let mut walk = Walk {
+        base_dir: dir,
+        options,
+        filter,
+        scan: Scan::default(),
+        visited: HashSet::new(),
+        targets: HashSet::new(),
+    };
+    walk.collect(dir, 0)?;
+    if !walk.targets.is_empty() {
+        walk.drop_duplicate_targets();
+    }
+    Ok(walk.scan)
+}
+
+/// [`scan_files`] without the ignored links.
+pub fn gather_files(
+    dir: &Path,
+    options: &ScanOptions,
+    filter: &Filter,
+) -> io::Result<Vec<PathBuf>> {
+    Ok(scan_files(dir, options, filter)?.files)
+}
+
+/// Lists the files directly inside `dir` with default scan options.
+pub fn list_files(dir: &Path) -> io::Result<Vec<PathBuf>> {
+    gather_files(dir, &ScanOptions::default(), &Filter::default())
+}
+
+/// Lists files below `dir`, not descending into folders `filter` excludes,
+/// `options.skip_dirs`, or deeper than `options.max_depth`.
+pub fn list_files_recursive(
+    dir: &Path,
+    options: &ScanOptions,
+    filter: &Filter,
+) -> io::Result<Vec<PathBuf>> {
+    let options = ScanOptions {
+        recursive: true,
+        ..options.clone()
+    };
+    gather_files(dir, &options, filter)
+}
+
+struct Walk<'a> {
+    base_dir: &'a Path,
+    options: &'a ScanOptions,
+    filter: &'a Filter,
+    scan: Scan,
+    /// Real paths of the folders scanned so far, when following links.
+    visited: HashSet<PathBuf>,
+    /// Real paths of files found through `SymlinkPolicy::MoveTarget` links.
+    targets: HashSet<PathBuf>,
+}
+
+impl Walk<'_> {
+    fn collect(&mut self, dir: &Path, depth: usize) -> io::Result<()> {
+        if self.options.symlinks == SymlinkPolicy::FollowDirs {
+            self.visited.insert(fs::canonicalize(dir)?);
+        }
+        for entry in fs::read_dir(dir)? {
+            let entry = entry?;
+            let file_type = entry.file_type()?;
+            let path = entry.path();
+            if file_type.is_symlink() {
+                self.symlink(path, depth)?;
+            } else if file_type.is_file() {
+                self.add_file(path);
+            } else if file_type.is_dir() && self.descends_into(&path, depth + 1) {
+                if self.skips_hidden_dir(&path) || self.seen_dir(&path) {
+                    continue;
+                }
+                self.scan.dirs.push(path.clone());
+                self.collect(&path, depth + 1)?;
             }
-            files.push(path);
-        } else if file_type.is_dir() {
-            collect_files_recursive(&path, files)?;
         }
+
+        Ok(())
+    }
+
+    fn symlink(&mut self, link: PathBuf, depth: usize) -> io::Result<()> {
+        // Broken links have no metadata and are never sorted.
+        let Ok(metadata) = fs::metadata(&link) else {
+            self.scan.ignored_links.push(link);
+            return Ok(());
+        };
+        if metadata.is_dir() && !self.options.recursive {
+            return Ok(());
+        }
+        match self.options.symlinks {
+            SymlinkPolicy::MoveLink if metadata.is_file() => self.add_file(link),
+            SymlinkPolicy::MoveTarget if metadata.is_file() => {
+                if self.allows_file(&link) {
+                    let target = fs::canonicalize(&link)?;
+                    if self.targets.insert(target.clone()) {
+                        self.scan.files.push(target);
+                    }
+                }
+            }
+            SymlinkPolicy::FollowDirs if metadata.is_dir() => {
+                if !self.descends_into(&link, depth + 1) || self.skips_hidden_dir(&link) {
+                    return Ok(());
+                }
+                if self.seen_dir(&link) {
+                    self.scan.ignored_links.push(link);
+                    return Ok(());
+                }
+                self.collect(&link, depth + 1)?;
+            }
+            _ => self.scan.ignored_links.push(link),
+        }
+        Ok(())
+    }
+
+    /// Lists each link target once, and not at all when the scan also found
+    /// it under its own path.
+    fn drop_duplicate_targets(&mut self) {
+        let direct: HashSet<PathBuf> = self
+            .scan
+            .files
+            .iter()
+            .filter(|file| !self.targets.contains(*file))
+            .filter_map(|file| fs::canonicalize(file).ok())
+            .collect();
+        let mut seen = HashSet::new();
+        self.scan.files.retain(|file| {
+            (!self.targets.contains(file) || !direct.contains(file)) && seen.insert(file.clone())
+        });
     }
 
-    Ok(())
+    fn add_file(&mut self, path: PathBuf) {
+        if !self.allows_file(&path) {
+            return;
+        }
+        if self.options.junk != JunkPolicy::Sort && hidden::is_junk(&path) {
+            self.scan.junk.push(path);
+        } else if !self.skips_hidden(&path) {
+            self.scan.files.push(path);
+        }
+    }
+
+    /// Counts and skips hidden files under `HiddenPolicy::Skip`.
+    fn skips_hidden(&mut self, path: &Path) -> bool {
+        let skip = self.options.hidden == HiddenPolicy::Skip && hidden::is_hidden(path);
+        self.scan.hidden += usize::from(skip);
+        skip
+    }
+
+    /// Counts and skips hidden folders unless they are `HiddenPolicy::Include`d;
+    /// sorting the insides of `.git` into `Hidden` would still break it.
+    fn skips_hidden_dir(&mut self, dir: &Path) -> bool {
+        let skip = self.options.hidden != HiddenPolicy::Include && hidden::is_hidden(dir);
+        self.scan.hidden += usize::from(skip);
+        skip
+    }
+
+    fn allows_file(&self, path: &Path) -> bool {
+        !is_state_file(path) && self.filter.allows_file(self.base_dir, path)
+    }
+
+    /// Whether following links already reached the real folder behind `dir`,
+    /// which also stops links that point back up the tree.
+    fn seen_dir(&self, dir: &Path) -> bool {
+        self.options.symlinks == SymlinkPolicy::FollowDirs
+            && fs::canonicalize(dir).is_ok_and(|real| self.visited.contains(&real))
+    }
+
+    fn descends_into(&self, dir: &Path, depth: usize) -> bool {
+        if !self.options.recursive {
+            return false;
+        }
+        if self.options.max_depth.is_some_and(|max| depth > max) {
+            return false;
+        }
+        if !self.filter.allows_dir(self.base_dir, dir) {
+            return false;
+        }
+        if self.options.skip_dirs.is_empty() {
+            return true;
+        }
+        match fs::canonicalize(dir) {
+            Ok(dir) => !self.options.skip_dirs.contains(&dir),
+            Err(_) => true,
+        }
+    }
 }
 
 fn is_state_file(path: &Path) -> bool {
-    matches!(path.file_name().and_then(|n| n.to_str()), Some(".rusty-sort-state.txt"))
+    matches!(
+        path.file_name().and_then(|n| n.to_str()),
+        Some(STATE_FILE_NAME) | Some(JOURNAL_FILE_NAME)
+    )
 }
 
+/// A single planned move of `source` to `target`.
 pub struct MovePlan {
+    /// The file to move.
     pub source: PathBuf,
+    /// Where it will end up.
     pub target: PathBuf,
+    /// The destination folder `target` was laid out under; see
+    /// [`Rules::destination`].
+    pub root: PathBuf,
+    /// The category it was classified into.
     pub category: Category,
+    /// Sniffed content type, when it disagrees with the file's extension.
+    pub mismatch: Option<&'static str>,
 }
 
+/// Totals from `apply_moves`.
+#[derive(Default)]
 pub struct MoveResult {
+    /// Whether files were moved, copied or linked.
+    pub mode: Mode,
+    /// Files moved, copied or linked into place, depending on `mode`.
     pub moved: usize,
+    /// Files left in place because their target existed.
     pub skipped: usize,
+    /// Files moved under a new name because their target existed.
+    pub renamed: usize,
+    /// Existing targets that were replaced.
+    pub overwritten: usize,
+    /// Sources dropped because the target already held the same content.
+    pub deduped: usize,
+    /// Files copied across filesystems because they could not be renamed.
+    pub copied: usize,
+    /// `Mode::Link` targets that had to be symlinks.
+    pub symlinked: usize,
+    /// Junk files deleted by the caller; `apply_moves` leaves this at 0.
+    pub junk_deleted: usize,
+    /// Empty folders removed by the caller; see [`crate::prune`].
+    pub removed_dirs: Vec<PathBuf>,
+    /// Files placed, per category.
     pub moved_by_category: CategoryCounts,
+    /// Files skipped, per category.
     pub skipped_by_category: CategoryCounts,
+    /// Files that could not be moved; they are left where they were.
+    pub errors: Vec<MoveError>,
+    /// Files that were moved, copied or linked but could not be recorded in
+    /// the journal, so `undo` will not revert them.
+    pub journal_errors: Vec<MoveError>,
 }
 
-#[derive(Default, Clone, Copy)]
+/// Per-category counters, kept in category order.
+#[derive(Default, Clone)]
 pub struct CategoryCounts {
-    pub images: usize,
-    pub documents: usize,
-    pub videos: usize,
-    pub audio: usize,
-    pub archives: usize,
-    pub others: usize,
+    counts: Vec<(Category, usize)>,
 }
 
 impl CategoryCounts {
-    fn inc(&mut self, category: Category) {
-        match category {
-            Category::Images => self.images += 1,
-            Category::Documents => self.documents += 1,
-            Category::Videos => self.videos += 1,
-            Category::Audio => self.audio += 1,
-            Category::Archives => self.archives += 1,
-            Category::Others => self.others += 1,
+    /// Starts every known category at zero so summaries list them all.
+    pub fn for_rules(rules: &Rules) -> Self {
+        Self {
+            counts: rules.categories().into_iter().map(|c| (c, 0)).collect(),
+        }
+    }
+
+    /// Adds one to `category`, appending it if it is not listed yet.
+    pub fn inc(&mut self, category: &Category) {
+        match self.counts.iter_mut().find(|(c, _)| c == category) {
+            Some((_, count)) => *count += 1,
+            None => self.counts.push((category.clone(), 1)),
         }
     }
+
+    /// Each category with its count.
+    pub fn iter(&self) -> impl Iterator<Item = (&Category, usize)> {
+        self.counts.iter().map(|(c, n)| (c, *n))
+    }
+}
+
+/// Tallies `files` per category.
+pub fn count_by_category(files: &[PathBuf], rules: &Rules) -> CategoryCounts {
+    let mut counts = CategoryCounts::for_rules(rules);
+    for file in files {
+        counts.inc(&rules.classify(file));
+    }
+    counts
 }
 
-pub fn plan_moves(dest_dir: &Path, files: &[PathBuf], rules: &Rules) -> Vec<MovePlan> {
+/// Plans where each file should go; files already at their target are left out.
+pub fn plan_moves(
+    dest_dir: &Path,
+    files: &[PathBuf],
+    rules: &Rules,
+    layout: &Layout,
+) -> Vec<MovePlan> {
     let mut plans = Vec::new();
 
     for file in files {
-        let category = rules.classify(file);
-        let file_name = match file.file_name() {
-            Some(name) => name,
-            None => continue,
-        };
-        let target_dir = dest_dir.join(category_folder_name(category));
-        let target_path = target_dir.join(file_name);
+        let classification = rules.classify_detailed(file);
+        let category = classification.category;
+        if file.file_name().is_none() {
+            continue;
+        }
+        let root = rules.destination(dest_dir, &category);
+        let target_path = layout.target(&root, file, &category);
 
         if *file == target_path {
             continue;
@@ -105,48 +431,417 @@ pub fn plan_moves(dest_dir: &Path, files: &[PathBuf], rules: &Rules) -> Vec<Move
         plans.push(MovePlan {
             source: file.clone(),
             target: target_path,
+            root,
             category,
+            mismatch: classification.mismatch,
         });
     }
 
     plans
 }
 
-pub fn apply_moves(plans: &[MovePlan]) -> io::Result<MoveResult> {
+/// For each plan, whether an earlier plan in `plans` already moves a file to
+/// the same target, so the conflict policy applies to it even though the
+/// target does not exist yet.
+pub fn claimed_targets(plans: &[MovePlan]) -> Vec<bool> {
+    let mut seen = HashSet::new();
+    plans
+        .iter()
+        .map(|plan| !seen.insert(plan.target.as_path()))
+        .collect()
+}
+
+/// Plans moving `sources` into `<dest_dir>/<folder>/`, filed under a
+/// category of the same name, numbering names that are already taken.
+pub fn plan_into_folder<'a>(
+    dest_dir: &Path,
+    folder: &str,
+    sources: impl IntoIterator<Item = &'a PathBuf>,
+) -> Vec<MovePlan> {
+    let root = dest_dir.join(folder);
+    let category = Category::new(folder);
+    let mut taken: HashSet<PathBuf> = HashSet::new();
+    let mut plans = Vec::new();
+    for source in sources {
+        let Some(name) = source.file_name() else {
+            continue;
+        };
+        let first = root.join(name);
+        let mut target = first.clone();
+        let mut n = 1;
+        while target.exists() || taken.contains(&target) {
+            target = conflict::numbered_name(&first, n);
+            n += 1;
+        }
+        taken.insert(target.clone());
+        plans.push(MovePlan {
+            source: source.clone(),
+            target,
+            root: dest_dir.to_path_buf(),
+            category: category.clone(),
+            mismatch: None,
+        });
+    }
+    plans
+}
+
+/// A file that could not be moved, with the reason.
+#[derive(Debug, Clone)]
+pub struct MoveError {
+    /// The file that stayed where it was.
+    pub source: PathBuf,
+    /// Why it could not be moved.
+    pub reason: String,
+}
+
+/// What happened to a single plan.
+enum Applied {
+    Moved {
+        renamed: bool,
+        overwritten: bool,
+        transfer: Transfer,
+        journaled: io::Result<()>,
+    },
+    Deduped,
+    Skipped,
+}
+
+/// How `apply_moves` carries out a plan.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ApplyOptions {
+    /// Move, copy or link each file.
+    pub mode: Mode,
+    /// What to do when a target already exists.
+    pub on_conflict: ConflictPolicy,
+    /// Return the first failure as an error instead of recording it in
+    /// `MoveResult::errors` and carrying on.
+    pub fail_fast: bool,
+}
+
+/// Carries out `plans` as `options` says, recording every completed transfer
+/// in `journal`.
+///
+/// A file that fails to move is recorded in `MoveResult::errors` and the
+/// remaining plans are still carried out, unless `options.fail_fast` is set,
+/// in which case the first failure is returned as an error.
+pub fn apply_moves(
+    plans: &[MovePlan],
+    rules: &Rules,
+    options: ApplyOptions,
+    journal: &mut Journal,
+) -> io::Result<MoveResult> {
+    let ApplyOptions {
+        mode,
+        on_conflict,
+        fail_fast,
+    } = options;
     let mut moved = 0usize;
     let mut skipped = 0usize;
-    let mut moved_by_category = CategoryCounts::default();
-    let mut skipped_by_category = CategoryCounts::default();
+    let mut renamed = 0usize;
+    let mut overwritten = 0usize;
+    let mut deduped = 0usize;
+    let mut copied = 0usize;
+    let mut symlinked = 0usize;
+    let mut moved_by_category = CategoryCounts::for_rules(rules);
+    let mut skipped_by_category = CategoryCounts::for_rules(rules);
+    let mut errors = Vec::new();
+    let mut journal_errors = Vec::new();
 
     for plan in plans {
-        if plan.target.exists() {
-            skipped += 1;
-            skipped_by_category.inc(plan.category);
-            continue;
-        }
-        if let Some(parent) = plan.target.parent() {
-            fs::create_dir_all(parent)?;
+        match apply_one(plan, mode, on_conflict, journal) {
+            Ok(Applied::Moved {
+                renamed: was_renamed,
+                overwritten: was_overwritten,
+                transfer,
+                journaled,
+            }) => {
+                if let Err(err) = journaled {
+                    journal_errors.push(MoveError {
+                        source: plan.source.clone(),
+                        reason: format!("not recorded in the journal: {}", err),
+                    });
+                }
+                renamed += usize::from(was_renamed);
+                overwritten += usize::from(was_overwritten);
+                copied += usize::from(matches!(transfer, Transfer::Copied));
+                symlinked += usize::from(matches!(transfer, Transfer::Symlinked));
+                moved += 1;
+                moved_by_category.inc(&plan.category);
+            }
+            Ok(Applied::Deduped) => deduped += 1,
+            Ok(Applied::Skipped) => {
+                skipped += 1;
+                skipped_by_category.inc(&plan.category);
+            }
+            Err(err) if fail_fast => {
+                return Err(io::Error::new(
+                    err.kind(),
+                    format!("{}: {}", plan.source.display(), err),
+                ));
+            }
+            Err(err) => errors.push(MoveError {
+                source: plan.source.clone(),
+                reason: err.to_string(),
+            }),
         }
-        fs::rename(&plan.source, &plan.target)?;
-        moved += 1;
-        moved_by_category.inc(plan.category);
     }
 
     Ok(MoveResult {
+        mode,
         moved,
         skipped,
+        renamed,
+        overwritten,
+        deduped,
+        copied,
+        symlinked,
+        junk_deleted: 0,
+        removed_dirs: Vec::new(),
         moved_by_category,
         skipped_by_category,
+        errors,
+        journal_errors,
     })
 }
 
-fn category_folder_name(category: Category) -> &'static str {
-    match category {
-        Category::Images => "Images",
-        Category::Documents => "Documents",
-        Category::Videos => "Videos",
-        Category::Audio => "Audio",
-        Category::Archives => "Archives",
-        Category::Others => "Others",
+fn apply_one(
+    plan: &MovePlan,
+    mode: Mode,
+    on_conflict: ConflictPolicy,
+    journal: &mut Journal,
+) -> io::Result<Applied> {
+    let (target, overwritten) = match conflict::resolve(on_conflict, &plan.source, &plan.target)? {
+        Resolution::Move(target) => (target, false),
+        Resolution::Overwrite => (plan.target.clone(), true),
+        Resolution::RemoveSource => {
+            // Copies and links never touch the original.
+            if mode == Mode::Move {
+                fs::remove_file(&plan.source)?;
+            }
+            return Ok(Applied::Deduped);
+        }
+        Resolution::Skip => return Ok(Applied::Skipped),
+    };
+    if let Some(parent) = target.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let transfer = transfer::transfer(mode, &plan.source, &target)?;
+    // The file is in place by now, so a journal failure is not a failed move.
+    let journaled = journal.record(&plan.source, &target, mode);
+    Ok(Applied::Moved {
+        renamed: target != plan.target,
+        overwritten,
+        transfer,
+        journaled,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        apply_moves, claimed_targets, plan_moves, scan_files, ApplyOptions, MovePlan, ScanOptions,
+        SymlinkPolicy,
+    };
+    use crate::filter::Filter;
+    use crate::hidden::{HiddenPolicy, JunkPolicy};
+    use crate::journal::Journal;
+    use crate::layout::Layout;
+    use crate::rules::{Category, Rules};
+    use std::env;
+    use std::fs;
+
+    #[test]
+    fn failed_moves_are_collected_and_the_rest_applied() {
+        let dir = env::temp_dir().join(format!("rusty-sort-apply-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("b.txt"), "b").unwrap();
+
+        let plan = |name: &str| MovePlan {
+            source: dir.join(name),
+            target: dir.join("Documents").join(name),
+            root: dir.clone(),
+            category: Category::new("Documents"),
+            mismatch: None,
+        };
+        let plans = [plan("missing.txt"), plan("b.txt")];
+        let rules = Rules::default();
+
+        let mut journal = Journal::new(&dir);
+        let result = apply_moves(&plans, &rules, ApplyOptions::default(), &mut journal).unwrap();
+        assert_eq!(result.moved, 1);
+        assert_eq!(result.errors.len(), 1);
+        assert_eq!(result.errors[0].source, dir.join("missing.txt"));
+
+        let mut journal = Journal::new(&dir);
+        let fail_fast = ApplyOptions {
+            fail_fast: true,
+            ..ApplyOptions::default()
+        };
+        assert!(apply_moves(&plans[..1], &rules, fail_fast, &mut journal).is_err());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn later_plans_for_a_claimed_target_are_marked() {
+        let dir = env::temp_dir().join(format!("rusty-sort-claimed-{}", std::process::id()));
+        let files = [dir.join("a/r.txt"), dir.join("b/r.txt"), dir.join("c.txt")];
+        let plans = plan_moves(&dir, &files, &Rules::default(), &Layout::default());
+        assert_eq!(plans[0].target, plans[1].target);
+        assert_eq!(claimed_targets(&plans), [false, true, false]);
+    }
+
+    #[test]
+    fn journal_failures_are_not_failed_moves() {
+        let dir = env::temp_dir().join(format!("rusty-sort-unjournaled-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("a.txt"), "a").unwrap();
+
+        let plans = [MovePlan {
+            source: dir.join("a.txt"),
+            target: dir.join("Documents/a.txt"),
+            root: dir.clone(),
+            category: Category::new("Documents"),
+            mismatch: None,
+        }];
+        // The journal's folder does not exist, so recording fails.
+        let mut journal = Journal::new(&dir.join("missing"));
+        let fail_fast = ApplyOptions {
+            fail_fast: true,
+            ..ApplyOptions::default()
+        };
+        let result = apply_moves(&plans, &Rules::default(), fail_fast, &mut journal).unwrap();
+        assert_eq!(result.moved, 1);
+        assert!(result.errors.is_empty());
+        assert_eq!(result.journal_errors.len(), 1);
+        assert!(dir.join("Documents/a.txt").exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn symlink_policies() {
+        use std::os::unix::fs::symlink;
+        use std::path::PathBuf;
+
+        let dir = env::temp_dir().join(format!("rusty-sort-links-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("sub")).unwrap();
+        fs::write(dir.join("sub/a.txt"), "a").unwrap();
+        symlink(dir.join("sub/a.txt"), dir.join("link.txt")).unwrap();
+        symlink(dir.join("sub"), dir.join("linked-sub")).unwrap();
+        symlink(&dir, dir.join("sub/loop")).unwrap();
+
+        let scan = |symlinks| {
+            let options = ScanOptions {
+                recursive: true,
+                symlinks,
+                ..ScanOptions::default()
+            };
+            let mut scan = scan_files(&dir, &options, &Filter::default()).unwrap();
+            scan.files.sort();
+            scan.ignored_links.sort();
+            scan
+        };
+        let name = |path: &PathBuf| path.strip_prefix(&dir).unwrap().to_path_buf();
+
+        let ignore = scan(SymlinkPolicy::Ignore);
+        assert_eq!(ignore.files, [dir.join("sub/a.txt")]);
+        assert_eq!(ignore.ignored_links.len(), 3);
+
+        let links = scan(SymlinkPolicy::MoveLink);
+        assert_eq!(links.files, [dir.join("link.txt"), dir.join("sub/a.txt")]);
+
+        // The target is already in the scan, so it is listed once.
+        let targets = scan(SymlinkPolicy::MoveTarget);
+        assert_eq!(targets.files, [dir.join("sub/a.txt")]);
+
+        // `sub` is reached once, through whichever path comes first, and the
+        // loop back to the source is reported instead of followed.
+        let follow = scan(SymlinkPolicy::FollowDirs);
+        assert_eq!(follow.files.len(), 1);
+        let ignored: Vec<_> = follow.ignored_links.iter().map(name).collect();
+        assert!(ignored.contains(&PathBuf::from("link.txt")));
+        assert!(ignored.iter().any(|link| link.ends_with("loop")));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn hidden_and_junk_files_are_set_aside() {
+        let dir = env::temp_dir().join(format!("rusty-sort-hidden-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join(".git")).unwrap();
+        for name in ["a.txt", ".bashrc", ".DS_Store", "Thumbs.db", ".git/config"] {
+            fs::write(dir.join(name), "x").unwrap();
+        }
+
+        let scan = |hidden, junk| {
+            let options = ScanOptions {
+                recursive: true,
+                hidden,
+                junk,
+                ..ScanOptions::default()
+            };
+            let mut scan = scan_files(&dir, &options, &Filter::default()).unwrap();
+            scan.files.sort();
+            scan.junk.sort();
+            scan
+        };
+
+        let default = scan(HiddenPolicy::Skip, JunkPolicy::Skip);
+        assert_eq!(default.files, [dir.join("a.txt")]);
+        assert_eq!(default.junk, [dir.join(".DS_Store"), dir.join("Thumbs.db")]);
+        assert_eq!(default.hidden, 2);
+
+        let all = scan(HiddenPolicy::Include, JunkPolicy::Sort);
+        assert_eq!(all.files.len(), 5);
+        assert!(all.junk.is_empty());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn hidden_category_leaves_hidden_folders_alone() {
+        let dir = env::temp_dir().join(format!("rusty-sort-hidden-dirs-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join(".git/objects")).unwrap();
+        fs::create_dir_all(dir.join("notes")).unwrap();
+        for name in [
+            "a.txt",
+            ".bashrc",
+            ".git/config",
+            ".git/objects/abc",
+            "notes/.todo",
+        ] {
+            fs::write(dir.join(name), "x").unwrap();
+        }
+
+        let options = ScanOptions {
+            recursive: true,
+            hidden: HiddenPolicy::Category,
+            ..ScanOptions::default()
+        };
+        let mut scan = scan_files(&dir, &options, &Filter::default()).unwrap();
+        scan.files.sort();
+        assert_eq!(
+            scan.files,
+            [
+                dir.join(".bashrc"),
+                dir.join("a.txt"),
+                dir.join("notes/.todo")
+            ]
+        );
+        assert_eq!(scan.dirs, [dir.join("notes")]);
+        assert_eq!(scan.hidden, 1);
+
+        let rules = Rules::default().with_hidden_category(true);
+        let plans = plan_moves(&dir, &scan.files, &rules, &Layout::default());
+        let hidden: Vec<_> = plans
+            .iter()
+            .filter(|plan| plan.category.name() == "Hidden")
+            .map(|plan| plan.source.clone())
+            .collect();
+        assert_eq!(hidden, [dir.join(".bashrc"), dir.join("notes/.todo")]);
+        fs::remove_dir_all(&dir).unwrap();
     }
 }
@@ -0,0 +1,364 @@
+//! Removing folders that a recursive sort left empty.
+
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::conflict::{self, ConflictPolicy, Resolution};
+use crate::dedupe::DUPLICATES_DIR;
+use crate::hidden::QUARANTINE_DIR;
+use crate::layout::Layout;
+use crate::organizer::{MoveError, MovePlan};
+use crate::rules::Rules;
+
+/// Which empty folders are removed after the moves.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum EmptyDirs {
+    /// Folders this run emptied; folders that were already empty stay.
+    #[default]
+    Emptied,
+    /// Never remove folders.
+    Keep,
+    /// Every empty folder the scan walked through.
+    All,
+}
+
+impl EmptyDirs {
+    /// Parses `emptied`, `keep` or `all`.
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "emptied" => Some(EmptyDirs::Emptied),
+            "keep" => Some(EmptyDirs::Keep),
+            "all" => Some(EmptyDirs::All),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for EmptyDirs {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            EmptyDirs::Emptied => "emptied",
+            EmptyDirs::Keep => "keep",
+            EmptyDirs::All => "all",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Removes the scanned subfolders a run leaves empty. The source folder
+/// itself is never among them, and `protected` folders (destination roots,
+/// category folders) are never removed.
+pub struct Pruner {
+    policy: EmptyDirs,
+    /// Deepest first, so parents are checked after their children.
+    dirs: Vec<PathBuf>,
+    empty_before: HashSet<PathBuf>,
+    protected: Vec<PathBuf>,
+}
+
+impl Pruner {
+    /// Takes the subfolders a scan walked and notes which are empty before
+    /// anything is moved.
+    pub fn new(policy: EmptyDirs, dirs: &[PathBuf], protected: &[PathBuf]) -> Self {
+        let mut dirs = dirs.to_vec();
+        dirs.sort_by_key(|dir| std::cmp::Reverse(dir.components().count()));
+        let empty_before = dirs.iter().filter(|dir| is_empty(dir)).cloned().collect();
+        Self {
+            policy,
+            dirs,
+            empty_before,
+            protected: protected.iter().map(|dir| absolute(dir)).collect(),
+        }
+    }
+
+    /// [`Pruner::new`], protecting every folder a sort into `dest_dir` writes
+    /// to: the destination roots, category folders, `Duplicates/` and
+    /// `Quarantine/`.
+    pub fn for_destination(
+        policy: EmptyDirs,
+        dirs: &[PathBuf],
+        dest_dir: &Path,
+        rules: &Rules,
+        layout: &Layout,
+    ) -> Self {
+        let mut protected = rules.destination_roots(dest_dir);
+        for category in rules.categories() {
+            let category_dest = rules.destination(dest_dir, &category);
+            protected.extend(layout.category_root(&category_dest, &category));
+        }
+        protected.push(dest_dir.join(DUPLICATES_DIR));
+        protected.push(dest_dir.join(QUARANTINE_DIR));
+        Self::new(policy, dirs, &protected)
+    }
+
+    /// The folders that will be removed once `plans` are carried out under
+    /// `on_conflict` and `deleted` files (junk) are gone, deepest first.
+    /// Sources the conflict policy will skip stay and keep their folder.
+    pub fn plan(
+        &self,
+        plans: &[MovePlan],
+        on_conflict: ConflictPolicy,
+        deleted: &[PathBuf],
+    ) -> Vec<PathBuf> {
+        // A new target claimed by an earlier plan will hold that plan's source
+        // by the time this one is carried out.
+        let mut claimants: HashMap<&Path, &Path> = HashMap::new();
+        let sources: HashSet<&Path> = plans
+            .iter()
+            .filter(|plan| {
+                let claimant = *claimants
+                    .entry(plan.target.as_path())
+                    .or_insert(plan.source.as_path());
+                let existing = if claimant == plan.source || plan.target.exists() {
+                    &plan.target
+                } else {
+                    claimant
+                };
+                leaves_source(&plan.source, existing, on_conflict)
+            })
+            .map(|plan| plan.source.as_path())
+            .chain(deleted.iter().map(PathBuf::as_path))
+            .collect();
+        let targets: Vec<PathBuf> = plans.iter().map(|plan| absolute(&plan.target)).collect();
+        let mut removed: HashSet<PathBuf> = HashSet::new();
+        for dir in &self.dirs {
+            if !self.may_remove(dir) {
+                continue;
+            }
+            let dir_abs = absolute(dir);
+            if targets.iter().any(|target| target.starts_with(&dir_abs)) {
+                continue;
+            }
+            let Ok(mut entries) = fs::read_dir(dir) else {
+                continue;
+            };
+            let emptied = entries.all(|entry| {
+                entry.is_ok_and(|entry| {
+                    let path = entry.path();
+                    sources.contains(path.as_path()) || removed.contains(&path)
+                })
+            });
+            if emptied {
+                removed.insert(dir.clone());
+            }
+        }
+        self.dirs
+            .iter()
+            .filter(|dir| removed.contains(*dir))
+            .cloned()
+            .collect()
+    }
+
+    /// Removes the folders that are empty now, deepest first. Returns the
+    /// removed folders and the ones that could not be removed.
+    pub fn prune(&self) -> (Vec<PathBuf>, Vec<MoveError>) {
+        let mut removed = Vec::new();
+        let mut errors = Vec::new();
+        for dir in &self.dirs {
+            if !self.may_remove(dir) || !is_empty(dir) {
+                continue;
+            }
+            match fs::remove_dir(dir) {
+                Ok(()) => removed.push(dir.clone()),
+                Err(err) => errors.push(MoveError {
+                    source: dir.clone(),
+                    reason: err.to_string(),
+                }),
+            }
+        }
+        (removed, errors)
+    }
+
+    fn may_remove(&self, dir: &Path) -> bool {
+        match self.policy {
+            EmptyDirs::Keep => false,
+            EmptyDirs::Emptied if self.empty_before.contains(dir) => false,
+            _ => !self.protected.contains(&absolute(dir)),
+        }
+    }
+}
+
+/// Whether moving `source` onto `existing` (the file that will be at its
+/// target, if any) takes the source away.
+fn leaves_source(source: &Path, existing: &Path, on_conflict: ConflictPolicy) -> bool {
+    !matches!(
+        conflict::resolve(on_conflict, source, existing),
+        Ok(Resolution::Skip) | Err(_)
+    )
+}
+
+fn is_empty(dir: &Path) -> bool {
+    fs::read_dir(dir).is_ok_and(|mut entries| entries.next().is_none())
+}
+
+fn absolute(path: &Path) -> PathBuf {
+    std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{EmptyDirs, Pruner};
+    use crate::conflict::ConflictPolicy;
+    use crate::layout::Layout;
+    use crate::organizer::MovePlan;
+    use crate::rules::{Category, Rules};
+    use std::env;
+    use std::fs;
+
+    #[test]
+    fn removes_only_what_the_run_emptied() {
+        let dir = env::temp_dir().join(format!("rusty-sort-prune-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        for sub in ["a/b", "old-empty", "c", "Documents"] {
+            fs::create_dir_all(dir.join(sub)).unwrap();
+        }
+        fs::write(dir.join("a/b/x.txt"), "x").unwrap();
+        fs::write(dir.join("c/y.txt"), "y").unwrap();
+        fs::write(dir.join("c/keep.bin"), "k").unwrap();
+
+        let dirs: Vec<_> = ["a", "a/b", "old-empty", "c", "Documents"]
+            .iter()
+            .map(|sub| dir.join(sub))
+            .collect();
+        let protected = [dir.join("Documents")];
+        let plans: Vec<_> = ["a/b/x.txt", "c/y.txt"]
+            .iter()
+            .map(|file| MovePlan {
+                source: dir.join(file),
+                target: dir.join("Documents").join(file.rsplit('/').next().unwrap()),
+                root: dir.clone(),
+                category: Category::new("Documents"),
+                mismatch: None,
+            })
+            .collect();
+
+        let pruner = Pruner::new(EmptyDirs::Emptied, &dirs, &protected);
+        assert_eq!(
+            pruner.plan(&plans, ConflictPolicy::Skip, &[]),
+            [dir.join("a/b"), dir.join("a")]
+        );
+
+        for plan in &plans {
+            fs::rename(&plan.source, &plan.target).unwrap();
+        }
+        let (removed, errors) = pruner.prune();
+        assert_eq!(removed, [dir.join("a/b"), dir.join("a")]);
+        assert!(errors.is_empty());
+        assert!(dir.join("old-empty").exists());
+
+        let (removed, _) = Pruner::new(EmptyDirs::All, &dirs, &protected).prune();
+        assert_eq!(removed, [dir.join("old-empty")]);
+        assert!(dir.join("Documents").exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn counts_deleted_junk_as_gone() {
+        let dir = env::temp_dir().join(format!("rusty-sort-prune-junk-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("photos")).unwrap();
+        fs::create_dir_all(dir.join("Documents")).unwrap();
+        fs::write(dir.join("photos/a.txt"), "a").unwrap();
+        fs::write(dir.join("photos/.DS_Store"), "junk").unwrap();
+
+        let plans = [MovePlan {
+            source: dir.join("photos/a.txt"),
+            target: dir.join("Documents/a.txt"),
+            root: dir.clone(),
+            category: Category::new("Documents"),
+            mismatch: None,
+        }];
+        let junk = [dir.join("photos/.DS_Store")];
+        let pruner = Pruner::new(EmptyDirs::Emptied, &[dir.join("photos")], &[]);
+        assert!(pruner.plan(&plans, ConflictPolicy::Skip, &[]).is_empty());
+        assert_eq!(
+            pruner.plan(&plans, ConflictPolicy::Skip, &junk),
+            [dir.join("photos")]
+        );
+
+        fs::rename(&plans[0].source, &plans[0].target).unwrap();
+        fs::remove_file(&junk[0]).unwrap();
+        assert_eq!(pruner.prune().0, [dir.join("photos")]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn keeps_folders_whose_files_will_be_skipped() {
+        let dir = env::temp_dir().join(format!("rusty-sort-prune-skip-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("sub")).unwrap();
+        fs::create_dir_all(dir.join("Documents")).unwrap();
+        fs::write(dir.join("sub/a.txt"), "new").unwrap();
+        fs::write(dir.join("Documents/a.txt"), "old").unwrap();
+
+        let plans = [MovePlan {
+            source: dir.join("sub/a.txt"),
+            target: dir.join("Documents/a.txt"),
+            root: dir.clone(),
+            category: Category::new("Documents"),
+            mismatch: None,
+        }];
+        let pruner = Pruner::new(EmptyDirs::Emptied, &[dir.join("sub")], &[]);
+        assert!(pruner.plan(&plans, ConflictPolicy::Skip, &[]).is_empty());
+        assert!(pruner.plan(&plans, ConflictPolicy::Dedupe, &[]).is_empty());
+        assert_eq!(
+            pruner.plan(&plans, ConflictPolicy::Rename, &[]),
+            [dir.join("sub")]
+        );
+        assert_eq!(
+            pruner.plan(&plans, ConflictPolicy::Overwrite, &[]),
+            [dir.join("sub")]
+        );
+
+        // Two files headed for the same new target: the second is skipped.
+        fs::remove_file(dir.join("Documents/a.txt")).unwrap();
+        fs::create_dir_all(dir.join("other")).unwrap();
+        fs::write(dir.join("other/a.txt"), "other").unwrap();
+        let claimed: Vec<_> = ["other/a.txt", "sub/a.txt"]
+            .iter()
+            .map(|file| MovePlan {
+                source: dir.join(file),
+                target: dir.join("Documents/a.txt"),
+                root: dir.clone(),
+                category: Category::new("Documents"),
+                mismatch: None,
+            })
+            .collect();
+        let pruner = Pruner::new(
+            EmptyDirs::Emptied,
+            &[dir.join("sub"), dir.join("other")],
+            &[],
+        );
+        assert_eq!(
+            pruner.plan(&claimed, ConflictPolicy::Skip, &[]),
+            [dir.join("other")]
+        );
+        assert_eq!(pruner.plan(&claimed, ConflictPolicy::Rename, &[]).len(), 2);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn protects_the_destination_folders() {
+        let dir = env::temp_dir().join(format!("rusty-sort-prune-dest-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let dirs: Vec<_> = ["Documents", "Duplicates", "Quarantine", "sub"]
+            .iter()
+            .map(|sub| dir.join(sub))
+            .collect();
+        for sub in &dirs {
+            fs::create_dir_all(sub).unwrap();
+        }
+
+        let pruner = Pruner::for_destination(
+            EmptyDirs::All,
+            &dirs,
+            &dir,
+            &Rules::default(),
+            &Layout::default(),
+        );
+        assert_eq!(pruner.prune().0, [dir.join("sub")]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
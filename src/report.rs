@@ -0,0 +1,196 @@
+//! JSON serialisation of scans, plans and results for machine consumers.
+
+use std::fmt::Write;
+use std::path::Path;
+
+use crate::conflict::ConflictPolicy;
+use crate::organizer::{CategoryCounts, MoveError, MovePlan, MoveResult};
+
+/// Builds a single JSON object, keeping fields in insertion order.
+#[derive(Debug, Clone, Default)]
+pub struct JsonObject {
+    fields: Vec<(String, String)>,
+}
+
+impl JsonObject {
+    /// An empty object.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a string field.
+    pub fn str(mut self, key: &str, value: &str) -> Self {
+        self.fields.push((key.to_string(), json_string(value)));
+        self
+    }
+
+    /// Adds a path as a string field.
+    pub fn path(self, key: &str, value: &Path) -> Self {
+        self.str(key, &value.to_string_lossy())
+    }
+
+    /// Adds a string field, or `null` for `None`.
+    pub fn opt_str(mut self, key: &str, value: Option<&str>) -> Self {
+        let value = value.map(json_string).unwrap_or_else(|| "null".to_string());
+        self.fields.push((key.to_string(), value));
+        self
+    }
+
+    /// Adds a number field.
+    pub fn num(mut self, key: &str, value: usize) -> Self {
+        self.fields.push((key.to_string(), value.to_string()));
+        self
+    }
+
+    /// Adds a boolean field.
+    pub fn bool(mut self, key: &str, value: bool) -> Self {
+        self.fields.push((key.to_string(), value.to_string()));
+        self
+    }
+
+    /// Adds an already serialised JSON value.
+    pub fn raw(mut self, key: &str, json: String) -> Self {
+        self.fields.push((key.to_string(), json));
+        self
+    }
+
+    /// Adds a nested object.
+    pub fn object(self, key: &str, value: JsonObject) -> Self {
+        self.raw(key, value.build())
+    }
+
+    /// Serialises the object.
+    pub fn build(&self) -> String {
+        let mut out = String::from("{");
+        for (idx, (key, value)) in self.fields.iter().enumerate() {
+            if idx > 0 {
+                out.push(',');
+            }
+            out.push_str(&json_string(key));
+            out.push(':');
+            out.push_str(value);
+        }
+        out.push('}');
+        out
+    }
+}
+
+/// Joins already serialised JSON values into an array.
+pub fn json_array(items: impl IntoIterator<Item = String>) -> String {
+    let items: Vec<String> = items.into_iter().collect();
+    format!("[{}]", items.join(","))
+}
+
+/// Quotes and escapes `value` as a JSON string.
+pub fn json_string(value: &str) -> String {
+    let mut out = String::with_capacity(value.len() + 2);
+    out.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => {
+                let _ = write!(out, "\\u{:04x}", c as u32);
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+/// `{"Images": 3, "Documents": 0, ...}` in category order.
+pub fn counts_json(counts: &CategoryCounts) -> JsonObject {
+    counts
+        .iter()
+        .fold(JsonObject::new(), |obj, (category, count)| {
+            obj.num(category.name(), count)
+        })
+}
+
+/// A planned move, including whether its target already exists or is
+/// `claimed` by an earlier move in the plan, and which conflict policy will
+/// then apply.
+pub fn plan_json(plan: &MovePlan, on_conflict: ConflictPolicy, claimed: bool) -> JsonObject {
+    let target_exists = plan.target.exists();
+    let clash = target_exists || claimed;
+    let policy = on_conflict.to_string();
+    JsonObject::new()
+        .path("source", &plan.source)
+        .path("target", &plan.target)
+        .path("root", &plan.root)
+        .str("category", plan.category.name())
+        .bool("target_exists", target_exists)
+        .bool("target_claimed", claimed)
+        .opt_str("conflict_policy", clash.then_some(policy.as_str()))
+        .opt_str("content_mismatch", plan.mismatch)
+}
+
+/// The totals of a run, plus its journal id when one was recorded.
+pub fn result_json(result: &MoveResult, run_id: Option<&str>) -> JsonObject {
+    JsonObject::new()
+        .str("mode", &result.mode.to_string())
+        .num("moved", result.moved)
+        .num("skipped", result.skipped)
+        .num("renamed", result.renamed)
+        .num("overwritten", result.overwritten)
+        .num("deduped", result.deduped)
+        .num("copied", result.copied)
+        .num("symlinked", result.symlinked)
+        .num("junk_deleted", result.junk_deleted)
+        .raw(
+            "removed_dirs",
+            json_array(
+                result
+                    .removed_dirs
+                    .iter()
+                    .map(|dir| json_string(&dir.to_string_lossy())),
+            ),
+        )
+        .opt_str("run_id", run_id)
+        .object("moved_by_category", counts_json(&result.moved_by_category))
+        .object(
+            "skipped_by_category",
+            counts_json(&result.skipped_by_category),
+        )
+        .raw("errors", errors_json(&result.errors))
+        .raw("journal_errors", errors_json(&result.journal_errors))
+}
+
+/// `[{"source": ..., "reason": ...}, ...]`
+pub fn errors_json(errors: &[MoveError]) -> String {
+    json_array(errors.iter().map(|error| {
+        JsonObject::new()
+            .path("source", &error.source)
+            .str("reason", &error.reason)
+            .build()
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{json_array, json_string, JsonObject};
+
+    #[test]
+    fn escapes_strings() {
+        assert_eq!(json_string("a\"b\\c"), "\"a\\\"b\\\\c\"");
+        assert_eq!(json_string("line\nnext\u{1}"), "\"line\\nnext\\u0001\"");
+        assert_eq!(json_string("C:\\Users\\me"), "\"C:\\\\Users\\\\me\"");
+    }
+
+    #[test]
+    fn builds_objects_in_order() {
+        let obj = JsonObject::new()
+            .str("name", "x")
+            .num("count", 2)
+            .opt_str("missing", None)
+            .raw("list", json_array(vec!["1".to_string(), "2".to_string()]));
+        assert_eq!(
+            obj.build(),
+            "{\"name\":\"x\",\"count\":2,\"missing\":null,\"list\":[1,2]}"
+        );
+    }
+}
@@ -1,39 +1,70 @@
+//! Categories and the rules that assign files to them.
+
 use std::collections::HashSet;
 use std::fmt;
 use std::io;
-use std::path::Path;
-
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-pub enum Category {
-    Images,
-    Documents,
-    Videos,
-    Audio,
-    Archives,
-    Others,
+use std::path::{Path, PathBuf};
+
+use crate::condition::Condition;
+use crate::filter::Filter;
+use crate::hidden;
+use crate::settings;
+use crate::sniff;
+
+const OTHERS: &str = "Others";
+
+/// A named category; also the name of the folder its files are moved into.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Category(String);
+
+impl Category {
+    /// A category with the given folder name; surrounding whitespace is trimmed.
+    pub fn new(name: &str) -> Self {
+        Self(name.trim().to_string())
+    }
+
+    /// The catch-all category for files no rule matches.
+    pub fn others() -> Self {
+        Self(OTHERS.to_string())
+    }
+
+    /// The category's name, as used for its folder.
+    pub fn name(&self) -> &str {
+        &self.0
+    }
+
+    /// Whether this is the catch-all `Others` category.
+    pub fn is_others(&self) -> bool {
+        self.0.eq_ignore_ascii_case(OTHERS)
+    }
+
+    fn matches(&self, name: &str) -> bool {
+        self.0.eq_ignore_ascii_case(name.trim())
+    }
 }
 
 impl fmt::Display for Category {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        let name = match self {
-            Category::Images => "Images",
-            Category::Documents => "Documents",
-            Category::Videos => "Videos",
-            Category::Audio => "Audio",
-            Category::Archives => "Archives",
-            Category::Others => "Others",
-        };
-        write!(f, "{}", name)
+        write!(f, "{}", self.0)
     }
 }
 
+const DEFAULTS: &[(&str, &[&str])] = &[
+    ("Images", IMAGES),
+    ("Documents", DOCUMENTS),
+    ("Videos", VIDEOS),
+    ("Audio", AUDIO),
+    ("Archives", ARCHIVES),
+];
+
 const IMAGES: &[&str] = &[
-    "jpg", "jpeg", "png", "gif", "bmp", "tiff", "tif", "webp", "svg", "heic", "heif", "ico",
-    "raw", "nef", "cr2", "arw", "dng",
+    "jpg", "jpeg", "png", "gif", "bmp", "tiff", "tif", "webp", "svg", "heic", "heif", "avif",
+    "ico", "raw", "nef", "cr2", "arw", "dng",
 ];
 const DOCUMENTS: &[&str] = &[
-    "pdf", "doc", "docx", "docm", "xls", "xlsx", "xlsm", "ppt", "pptx", "pptm", "txt", "md",
-    "rtf", "csv", "tsv", "json", "yaml", "yml", "xml", "log", "ini", "cfg",
+    "pdf", "doc", "docx", "docm", "dotx", "dotm", "xls", "xlsx", "xlsm", "xlsb", "xltx", "xltm",
+    "xlam", "ppt", "pptx", "pptm", "potx", "potm", "ppsx", "ppsm", "ppam", "txt", "md", "rtf",
+    "csv", "tsv", "json", "yaml", "yml", "xml", "log", "ini", "cfg",
 ];
 const VIDEOS: &[&str] = &[
     "mp4", "mkv", "mov", "avi", "wmv", "flv", "webm", "m4v", "3gp", "3g2", "mpg", "mpeg",
@@ -47,161 +78,528 @@ const ARCHIVES: &[&str] = &[
     "iso",
 ];
 
+#[derive(Clone)]
+struct CategoryRule {
+    category: Category,
+    extensions: HashSet<String>,
+}
+
+/// A rule that applies when all its conditions match, tried before any
+/// extension rule.
+#[derive(Clone)]
+struct ConditionRule {
+    category: Category,
+    conditions: Vec<Condition>,
+    priority: i64,
+}
+
+/// Maps file names and extensions to categories. Anything unmatched is `Others`.
+#[derive(Clone)]
 pub struct Rules {
-    images: HashSet<String>,
-    documents: HashSet<String>,
-    videos: HashSet<String>,
-    audio: HashSet<String>,
-    archives: HashSet<String>,
+    categories: Vec<CategoryRule>,
+    condition_rules: Vec<ConditionRule>,
+    /// Categories written somewhere other than the destination folder.
+    roots: Vec<(Category, PathBuf)>,
+    /// Category for every hidden file, if they are sorted separately.
+    hidden: Option<Category>,
+    filter: Filter,
+    sniff: bool,
 }
 
-impl Rules {
-    pub fn default() -> Self {
-        Self {
-            images: IMAGES.iter().map(|s| s.to_string()).collect(),
-            documents: DOCUMENTS.iter().map(|s| s.to_string()).collect(),
-            videos: VIDEOS.iter().map(|s| s.to_string()).collect(),
-            audio: AUDIO.iter().map(|s| s.to_string()).collect(),
-            archives: ARCHIVES.iter().map(|s| s.to_string()).collect(),
+/// The category picked for a file, plus the sniffed content type when it
+/// disagrees with the file's extension.
+pub struct Classification {
+    /// The category the file belongs to.
+    pub category: Category,
+    /// The sniffed content type, if it disagrees with the extension.
+    pub mismatch: Option<&'static str>,
+}
+
+impl Default for Rules {
+    fn default() -> Self {
+        let mut rules = Self {
+            categories: Vec::new(),
+            condition_rules: Vec::new(),
+            roots: Vec::new(),
+            hidden: None,
+            filter: Filter::default(),
+            sniff: false,
+        };
+        for (name, extensions) in DEFAULTS {
+            let category = rules.category(name);
+            for ext in extensions.iter() {
+                rules.insert(&category, ext.to_string());
+            }
         }
+        rules
     }
+}
 
+impl Rules {
+    /// Loads the default rules extended by a rules file; see [`crate::settings`].
     pub fn from_config(path: &Path) -> io::Result<Self> {
-        let mut rules = Self::default();
-        let content = std::fs::read_to_string(path)?;
-        for (idx, raw_line) in content.lines().enumerate() {
-            let line = raw_line.trim();
-            if line.is_empty() || line.starts_with('#') {
-                continue;
-            }
-            let (name, rest) = line.split_once('=').ok_or_else(|| {
-                io::Error::new(
-                    io::ErrorKind::InvalidInput,
-                    format!("Invalid config line {}: {}", idx + 1, raw_line),
-                )
-            })?;
-            let category = parse_category(name.trim()).ok_or_else(|| {
-                io::Error::new(
-                    io::ErrorKind::InvalidInput,
-                    format!("Unknown category on line {}: {}", idx + 1, raw_line),
-                )
-            })?;
-
-            for ext in rest.split(',') {
-                let ext = ext.trim().trim_start_matches('.').to_ascii_lowercase();
-                if ext.is_empty() {
-                    continue;
-                }
-                rules.insert(category, ext);
+        Ok(settings::load(path)?.rules)
+    }
+
+    /// Include/exclude patterns applied when scanning.
+    pub fn filter(&self) -> &Filter {
+        &self.filter
+    }
+
+    /// Mutable access to the scan filter, for adding patterns.
+    pub fn filter_mut(&mut self) -> &mut Filter {
+        &mut self.filter
+    }
+
+    /// All known categories in classification order, with `Others` last.
+    pub fn categories(&self) -> Vec<Category> {
+        let mut categories: Vec<Category> = self
+            .categories
+            .iter()
+            .map(|rule| rule.category.clone())
+            .collect();
+        categories.push(Category::others());
+        categories
+    }
+
+    /// The folder `category` is laid out under: its own root from the rules
+    /// file (relative roots are taken from `dest_dir`), or `dest_dir` itself.
+    pub fn destination(&self, dest_dir: &Path, category: &Category) -> PathBuf {
+        self.roots
+            .iter()
+            .find(|(c, _)| c == category)
+            .map_or_else(|| dest_dir.to_path_buf(), |(_, root)| dest_dir.join(root))
+    }
+
+    /// `dest_dir` followed by every other folder a category is written to.
+    pub fn destination_roots(&self, dest_dir: &Path) -> Vec<PathBuf> {
+        let mut roots = vec![dest_dir.to_path_buf()];
+        for (_, root) in &self.roots {
+            let root = dest_dir.join(root);
+            if !roots.contains(&root) {
+                roots.push(root);
             }
         }
+        roots
+    }
+
+    /// Enables content sniffing: files are classified by their leading bytes
+    /// when a known signature is found, falling back to the extension.
+    pub fn with_sniff(mut self, sniff: bool) -> Self {
+        self.sniff = sniff;
+        self
+    }
 
-        Ok(rules)
+    /// Sorts every hidden file into the `Hidden` category, ahead of any other
+    /// rule.
+    pub fn with_hidden_category(mut self, enabled: bool) -> Self {
+        self.hidden = enabled.then(|| self.category(hidden::HIDDEN_CATEGORY));
+        self
     }
 
+    /// Picks the category for `path`.
     pub fn classify(&self, path: &Path) -> Category {
-        let ext = path
-            .extension()
-            .and_then(|s| s.to_str())
-            .map(|s| s.to_ascii_lowercase());
+        self.classify_detailed(path).category
+    }
 
-        let Some(ext) = ext else {
-            return Category::Others;
-        };
+    /// Picks the category for `path` and reports any content mismatch.
+    pub fn classify_detailed(&self, path: &Path) -> Classification {
+        let hidden = self
+            .hidden
+            .as_ref()
+            .filter(|_| hidden::is_hidden(path))
+            .cloned();
+        if let Some(category) = hidden.or_else(|| self.classify_by_conditions(path)) {
+            return Classification {
+                category,
+                mismatch: None,
+            };
+        }
+
+        let ext = path.extension().and_then(|s| s.to_str());
+        let by_extension = self.classify_name(path);
 
-        if self.images.contains(&ext) {
-            Category::Images
-        } else if self.documents.contains(&ext) {
-            Category::Documents
-        } else if self.videos.contains(&ext) {
-            Category::Videos
-        } else if self.audio.contains(&ext) {
-            Category::Audio
-        } else if self.archives.contains(&ext) {
-            Category::Archives
+        let detected = if self.sniff {
+            sniff::detect(path).ok().flatten()
         } else {
-            Category::Others
+            None
+        };
+        let Some(detected) = detected else {
+            return Classification {
+                category: by_extension,
+                mismatch: None,
+            };
+        };
+        if !sniff::is_mismatch(ext, detected) || (sniff::is_generic(detected) && ext.is_some()) {
+            return Classification {
+                category: by_extension,
+                mismatch: None,
+            };
+        }
+
+        Classification {
+            category: self.classify_extension(detected),
+            mismatch: Some(detected),
         }
     }
 
-    fn insert(&mut self, category: Category, ext: String) {
-        match category {
-            Category::Images => {
-                self.images.insert(ext);
-            }
-            Category::Documents => {
-                self.documents.insert(ext);
-            }
-            Category::Videos => {
-                self.videos.insert(ext);
-            }
-            Category::Audio => {
-                self.audio.insert(ext);
-            }
-            Category::Archives => {
-                self.archives.insert(ext);
+    fn classify_by_conditions(&self, path: &Path) -> Option<Category> {
+        self.condition_rules
+            .iter()
+            .find(|rule| rule.conditions.iter().all(|c| c.matches(path)))
+            .map(|rule| rule.category.clone())
+    }
+
+    /// Classifies by the longest known extension, so a `tar.gz` rule wins
+    /// over `gz` for `backup.tar.gz`.
+    fn classify_name(&self, path: &Path) -> Category {
+        let Some(name) = path.file_name().and_then(|s| s.to_str()) else {
+            return Category::others();
+        };
+        let name = name.to_ascii_lowercase();
+        // A leading dot marks a hidden file, not an extension.
+        name.char_indices()
+            .skip(1)
+            .filter(|&(_, c)| c == '.')
+            .find_map(|(idx, _)| self.find_extension(&name[idx + 1..]))
+            .unwrap_or_else(Category::others)
+    }
+
+    fn classify_extension(&self, ext: &str) -> Category {
+        self.find_extension(&ext.to_ascii_lowercase())
+            .unwrap_or_else(Category::others)
+    }
+
+    fn find_extension(&self, ext: &str) -> Option<Category> {
+        self.categories
+            .iter()
+            .find(|rule| rule.extensions.contains(ext))
+            .map(|rule| rule.category.clone())
+    }
+
+    /// Looks up a category by name (case-insensitive), registering it if new.
+    pub(crate) fn category(&mut self, name: &str) -> Category {
+        if Category::new(name).is_others() {
+            return Category::others();
+        }
+        if let Some(rule) = self
+            .categories
+            .iter()
+            .find(|rule| rule.category.matches(name))
+        {
+            return rule.category.clone();
+        }
+        let category = Category::new(name);
+        self.categories.push(CategoryRule {
+            category: category.clone(),
+            extensions: HashSet::new(),
+        });
+        category
+    }
+
+    /// Assigns `ext` to `category`, taking it away from any other category so
+    /// the latest assignment wins. Returns the category it was taken from.
+    pub(crate) fn insert(&mut self, category: &Category, ext: String) -> Option<Category> {
+        let mut previous = None;
+        for rule in &mut self.categories {
+            if rule.category == *category {
+                rule.extensions.insert(ext.clone());
+            } else if rule.extensions.remove(&ext) {
+                previous = Some(rule.category.clone());
             }
-            Category::Others => {}
         }
+        previous
+    }
+
+    /// Writes `category` under `root` instead of the destination folder.
+    pub(crate) fn set_destination(&mut self, category: Category, root: PathBuf) {
+        self.roots.retain(|(c, _)| *c != category);
+        self.roots.push((category, root));
     }
-}
 
-fn parse_category(name: &str) -> Option<Category> {
-    match name.trim().to_ascii_lowercase().as_str() {
-        "images" => Some(Category::Images),
-        "documents" => Some(Category::Documents),
-        "videos" => Some(Category::Videos),
-        "audio" => Some(Category::Audio),
-        "archives" => Some(Category::Archives),
-        "others" => Some(Category::Others),
-        _ => None,
+    /// Adds a condition rule after any rules of the same or higher priority.
+    pub(crate) fn add_condition_rule(
+        &mut self,
+        category: Category,
+        conditions: Vec<Condition>,
+        priority: i64,
+    ) {
+        let at = self
+            .condition_rules
+            .iter()
+            .position(|rule| rule.priority < priority)
+            .unwrap_or(self.condition_rules.len());
+        self.condition_rules.insert(
+            at,
+            ConditionRule {
+                category,
+                conditions,
+                priority,
+            },
+        );
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::{Category, Rules};
-    use std::path::Path;
+    use crate::layout::Layout;
+    use crate::{organizer, settings};
+    use std::io;
+    use std::path::{Path, PathBuf};
+    use std::time::{Duration, SystemTime};
+
+    fn parse(content: &str) -> io::Result<Rules> {
+        settings::parse_lines(content).map(|settings| settings.rules)
+    }
 
     #[test]
     fn classify_images() {
         let rules = Rules::default();
-        assert_eq!(rules.classify(Path::new("photo.JPG")), Category::Images);
-        assert_eq!(rules.classify(Path::new("icon.png")), Category::Images);
+        assert_eq!(
+            rules.classify(Path::new("photo.JPG")),
+            Category::new("Images")
+        );
+        assert_eq!(
+            rules.classify(Path::new("icon.png")),
+            Category::new("Images")
+        );
     }
 
     #[test]
     fn classify_documents() {
         let rules = Rules::default();
-        assert_eq!(rules.classify(Path::new("report.pdf")), Category::Documents);
-        assert_eq!(rules.classify(Path::new("notes.md")), Category::Documents);
+        assert_eq!(
+            rules.classify(Path::new("report.pdf")),
+            Category::new("Documents")
+        );
+        assert_eq!(
+            rules.classify(Path::new("notes.md")),
+            Category::new("Documents")
+        );
     }
 
     #[test]
     fn classify_videos() {
         let rules = Rules::default();
-        assert_eq!(rules.classify(Path::new("movie.mkv")), Category::Videos);
-        assert_eq!(rules.classify(Path::new("clip.MP4")), Category::Videos);
+        assert_eq!(
+            rules.classify(Path::new("movie.mkv")),
+            Category::new("Videos")
+        );
+        assert_eq!(
+            rules.classify(Path::new("clip.MP4")),
+            Category::new("Videos")
+        );
     }
 
     #[test]
     fn classify_audio() {
         let rules = Rules::default();
-        assert_eq!(rules.classify(Path::new("song.mp3")), Category::Audio);
-        assert_eq!(rules.classify(Path::new("voice.WAV")), Category::Audio);
+        assert_eq!(
+            rules.classify(Path::new("song.mp3")),
+            Category::new("Audio")
+        );
+        assert_eq!(
+            rules.classify(Path::new("voice.WAV")),
+            Category::new("Audio")
+        );
     }
 
     #[test]
     fn classify_archives() {
         let rules = Rules::default();
-        assert_eq!(rules.classify(Path::new("backup.zip")), Category::Archives);
-        assert_eq!(rules.classify(Path::new("bundle.tar")), Category::Archives);
+        assert_eq!(
+            rules.classify(Path::new("backup.zip")),
+            Category::new("Archives")
+        );
+        assert_eq!(
+            rules.classify(Path::new("bundle.tar")),
+            Category::new("Archives")
+        );
     }
 
     #[test]
     fn classify_others() {
         let rules = Rules::default();
-        assert_eq!(rules.classify(Path::new("file")), Category::Others);
-        assert_eq!(rules.classify(Path::new("weird.ext")), Category::Others);
+        assert_eq!(rules.classify(Path::new("file")), Category::others());
+        assert_eq!(rules.classify(Path::new("weird.ext")), Category::others());
+    }
+
+    #[test]
+    fn config_adds_custom_categories() {
+        let rules = parse("Fonts=ttf,.OTF\ncode=rs,py\nimages=psd\n").unwrap();
+        assert_eq!(
+            rules.classify(Path::new("font.otf")),
+            Category::new("Fonts")
+        );
+        assert_eq!(rules.classify(Path::new("main.rs")), Category::new("code"));
+        assert_eq!(
+            rules.classify(Path::new("art.psd")),
+            Category::new("Images")
+        );
+
+        let names: Vec<String> = rules
+            .categories()
+            .iter()
+            .map(|c| c.name().to_string())
+            .collect();
+        assert_eq!(
+            names,
+            [
+                "Images",
+                "Documents",
+                "Videos",
+                "Audio",
+                "Archives",
+                "Fonts",
+                "code",
+                "Others"
+            ]
+        );
+    }
+
+    #[test]
+    fn compound_extensions_use_longest_match() {
+        let rules = parse(
+            "Backups=tar.gz
+Scripts=.user.js
+",
+        )
+        .unwrap();
+        assert_eq!(
+            rules.classify(Path::new("home.TAR.GZ")),
+            Category::new("Backups")
+        );
+        assert_eq!(
+            rules.classify(Path::new("dump.gz")),
+            Category::new("Archives")
+        );
+        assert_eq!(
+            rules.classify(Path::new("adblock.user.js")),
+            Category::new("Scripts")
+        );
+        assert_eq!(
+            rules.classify(Path::new("my.report.pdf")),
+            Category::new("Documents")
+        );
+        assert_eq!(
+            rules.classify(Path::new(".tar.gz")),
+            Category::new("Archives")
+        );
+    }
+
+    #[test]
+    fn condition_rules_win_over_extensions_by_priority() {
+        let rules = parse(
+            "Invoices: name ~ /^INV-\\d+/\n\
+             Screenshots: name ~ /^screenshot .*\\.png$/i priority 5\n\
+             Shots: name ~ /\\.png$/ priority 5\n",
+        )
+        .unwrap();
+        assert_eq!(
+            rules.classify(Path::new("INV-0042.pdf")),
+            Category::new("Invoices")
+        );
+        assert_eq!(
+            rules.classify(Path::new("Screenshot 2024-05-01.png")),
+            Category::new("Screenshots")
+        );
+        assert_eq!(
+            rules.classify(Path::new("logo.png")),
+            Category::new("Shots")
+        );
+        assert_eq!(
+            rules.classify(Path::new("INV-draft.pdf")),
+            Category::new("Documents")
+        );
+
+        assert!(parse("Bad: name ~ /(unclosed/\n").is_err());
+        assert!(parse("Bad: name ~ /x/ priority high\n").is_err());
+    }
+
+    #[test]
+    fn config_extensions_override_defaults() {
+        let rules = parse("Images=pdf\nCode=py\nScripts=py\n").unwrap();
+        assert_eq!(
+            rules.classify(Path::new("scan.pdf")),
+            Category::new("Images")
+        );
+        assert_eq!(
+            rules.classify(Path::new("tool.py")),
+            Category::new("Scripts")
+        );
+    }
+
+    #[test]
+    fn config_reads_scan_patterns() {
+        let rules = parse("exclude=*.part, .git/**\nImages=psd\n").unwrap();
+        let base = Path::new("in");
+        assert!(!rules
+            .filter()
+            .allows_file(base, Path::new("in/movie.mkv.part")));
+        assert!(!rules.filter().allows_dir(base, Path::new("in/.git")));
+        assert!(rules.filter().allows_file(base, Path::new("in/art.psd")));
+    }
+
+    #[test]
+    fn config_rejects_path_like_category_names() {
+        assert!(parse("../Escape=txt\n").is_err());
+        assert!(parse("=txt\n").is_err());
+        assert!(parse("/Root=txt\n").is_err());
+        assert!(parse("Archive//Old=txt\n").is_err());
+        assert!(parse("Archive/Old=bak\n").is_ok());
+    }
+
+    #[test]
+    fn size_rules_read_metadata() {
+        let dir = std::env::temp_dir().join(format!("rusty-sort-size-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let big = dir.join("big.iso");
+        let small = dir.join("small.iso");
+        std::fs::write(&big, [0u8; 2048]).unwrap();
+        std::fs::write(&small, [0u8; 16]).unwrap();
+
+        let old = dir.join("old.txt");
+        std::fs::write(&old, "old").unwrap();
+        let mtime = SystemTime::now() - Duration::from_secs(30 * 24 * 60 * 60);
+        std::fs::File::options()
+            .write(true)
+            .open(&old)
+            .unwrap()
+            .set_modified(mtime)
+            .unwrap();
+
+        let rules = parse("Large: size > 1KB\nArchive/Old: modified older than 1d\n").unwrap();
+        assert_eq!(rules.classify(&big), Category::new("Large"));
+        assert_eq!(rules.classify(&small), Category::new("Archives"));
+        assert_eq!(rules.classify(&old), Category::new("Archive/Old"));
+        let plans = organizer::plan_moves(&dir, &[old], &rules, &Layout::default());
+        assert_eq!(plans[0].target, dir.join("Archive/Old/old.txt"));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn categories_can_have_their_own_destination() {
+        let rules =
+            parse("Videos.destination = media\nArchives.destination=/cold\nFonts=ttf\n").unwrap();
+        let dest = Path::new("out");
+        assert_eq!(
+            rules.destination(dest, &Category::new("Videos")),
+            PathBuf::from("out/media")
+        );
+        assert_eq!(
+            rules.destination(dest, &Category::new("Archives")),
+            PathBuf::from("/cold")
+        );
+        assert_eq!(rules.destination(dest, &Category::new("Fonts")), dest);
+        assert_eq!(
+            rules.destination_roots(dest),
+            [
+                PathBuf::from("out"),
+                PathBuf::from("out/media"),
+                PathBuf::from("/cold")
+            ]
+        );
+        assert!(parse("Videos.destination=  \n").is_err());
     }
 }
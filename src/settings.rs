@@ -0,0 +1,648 @@
+//! Rules files: the line-based `Category=ext,ext` format and TOML, plus
+//! `config check` diagnostics.
+
+use std::env;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::condition::{Condition, NamePattern};
+use crate::conflict::ConflictPolicy;
+use crate::filter::Glob;
+use crate::layout::Layout;
+use crate::rules::{Category, Rules};
+use crate::toml::{self, Item, Table, Value};
+
+/// Everything a rules file can set.
+#[derive(Clone)]
+pub struct Settings {
+    /// The categories and their extensions and conditions.
+    pub rules: Rules,
+    /// The `on_conflict` setting, if the file has one.
+    pub on_conflict: Option<ConflictPolicy>,
+    /// The `layout` setting, if the file has one.
+    pub layout: Option<Layout>,
+    /// Problems that don't stop the file from loading, such as unknown keys.
+    pub warnings: Vec<Diagnostic>,
+    /// Extensions the file assigns to a category while they already belonged
+    /// to another one (a default category or an earlier line). The last
+    /// assignment wins.
+    pub overlaps: Vec<Diagnostic>,
+}
+
+/// A problem found in a rules file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    /// 1-based line number in the rules file.
+    pub line: usize,
+    /// What is wrong.
+    pub message: String,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+/// Loads a rules file; files ending in `.toml` are read as TOML.
+pub fn load(path: &Path) -> io::Result<Settings> {
+    Ok(read(path)?.build())
+}
+
+/// Loads a rules file and reports unknown keys, extensions mapped to more
+/// than one category and rules that can never match, ordered by line.
+pub fn check(path: &Path) -> io::Result<Vec<Diagnostic>> {
+    let spec = read(path)?;
+    let unreachable = spec.unreachable_rules();
+    let settings = spec.build();
+    let mut diagnostics = settings.warnings;
+    diagnostics.extend(settings.overlaps);
+    diagnostics.extend(unreachable);
+    diagnostics.sort_by_key(|diagnostic| diagnostic.line);
+    Ok(diagnostics)
+}
+
+/// Parses the contents of a line-based `Category=ext,ext` rules file.
+pub fn parse_lines(content: &str) -> io::Result<Settings> {
+    Ok(Spec::from_lines(content)?.build())
+}
+
+/// Parses the contents of a TOML rules file.
+pub fn parse_toml(content: &str) -> io::Result<Settings> {
+    Ok(Spec::from_toml(&toml::parse(content)?)?.build())
+}
+
+fn read(path: &Path) -> io::Result<Spec> {
+    let content = fs::read_to_string(path)?;
+    let is_toml = path
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("toml"));
+    if is_toml {
+        Spec::from_toml(&toml::parse(&content)?)
+    } else {
+        Spec::from_lines(&content)
+    }
+}
+
+/// A rules file as written, before it is merged into the default rules.
+#[derive(Default)]
+struct Spec {
+    categories: Vec<CategorySpec>,
+    rules: Vec<RuleSpec>,
+    excludes: Vec<Glob>,
+    includes: Vec<Glob>,
+    on_conflict: Option<ConflictPolicy>,
+    layout: Option<Layout>,
+    unknown: Vec<Diagnostic>,
+}
+
+struct CategorySpec {
+    name: String,
+    extensions: Vec<(String, usize)>,
+    destination: Option<PathBuf>,
+}
+
+struct RuleSpec {
+    category: String,
+    conditions: Vec<Condition>,
+    priority: i64,
+    line: usize,
+}
+
+impl Spec {
+    fn from_lines(content: &str) -> io::Result<Self> {
+        let mut spec = Self::default();
+        for (idx, raw_line) in content.lines().enumerate() {
+            let line = raw_line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            if let Some((name, rule)) = split_condition_rule(line) {
+                check_category_name(name, idx + 1, raw_line)?;
+                let (condition, priority) = parse_condition_rule(rule)
+                    .map_err(|reason| invalid(idx + 1, "Invalid rule", &reason))?;
+                spec.rules.push(RuleSpec {
+                    category: name.to_string(),
+                    conditions: vec![condition],
+                    priority,
+                    line: idx + 1,
+                });
+                continue;
+            }
+            let (name, rest) = line.split_once('=').ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("Invalid config line {}: {}", idx + 1, raw_line),
+                )
+            })?;
+            let name = name.trim();
+            let directive = name.to_ascii_lowercase();
+            if directive == "exclude" || directive == "include" {
+                for pattern in rest.split(',').filter(|p| !p.trim().is_empty()) {
+                    let glob = Glob::parse(pattern)?;
+                    if directive == "exclude" {
+                        spec.excludes.push(glob);
+                    } else {
+                        spec.includes.push(glob);
+                    }
+                }
+                continue;
+            }
+            if let Some((category, key)) = name.rsplit_once('.') {
+                if key.trim().eq_ignore_ascii_case("destination") {
+                    let category = category.trim();
+                    check_category_name(category, idx + 1, raw_line)?;
+                    spec.categories.push(CategorySpec {
+                        name: category.to_string(),
+                        extensions: Vec::new(),
+                        destination: Some(parse_destination(rest, idx + 1)?),
+                    });
+                    continue;
+                }
+            }
+            check_category_name(name, idx + 1, raw_line)?;
+            spec.categories.push(CategorySpec {
+                name: name.to_string(),
+                extensions: rest
+                    .split(',')
+                    .filter_map(normalize_extension)
+                    .map(|ext| (ext, idx + 1))
+                    .collect(),
+                destination: None,
+            });
+        }
+        Ok(spec)
+    }
+
+    fn from_toml(root: &Table) -> io::Result<Self> {
+        let mut spec = Self::default();
+        for (key, item) in root.iter() {
+            match key {
+                "categories" => spec.toml_categories(table(item, key)?)?,
+                "rules" => {
+                    let Value::Array(rules) = &item.value else {
+                        return Err(type_error(item, key, "an array of tables ([[rules]])"));
+                    };
+                    for rule in rules {
+                        spec.toml_rule(rule)?;
+                    }
+                }
+                "ignore" => {
+                    for (name, value) in table(item, key)?.iter() {
+                        let globs = match name {
+                            "exclude" => &mut spec.excludes,
+                            "include" => &mut spec.includes,
+                            _ => {
+                                spec.unknown_key(value, &format!("ignore.{}", name));
+                                continue;
+                            }
+                        };
+                        for (pattern, line) in strings(value, name)? {
+                            globs.push(Glob::parse(&pattern).map_err(|err| {
+                                invalid(line, "Invalid pattern", &err.to_string())
+                            })?);
+                        }
+                    }
+                }
+                "conflict" => {
+                    for (name, value) in table(item, key)?.iter() {
+                        if name != "policy" {
+                            spec.unknown_key(value, &format!("conflict.{}", name));
+                            continue;
+                        }
+                        let policy = string(value, name)?;
+                        spec.on_conflict =
+                            Some(ConflictPolicy::parse(policy).ok_or_else(|| {
+                                invalid(value.line, "Unknown conflict policy", policy)
+                            })?);
+                    }
+                }
+                "layout" => {
+                    for (name, value) in table(item, key)?.iter() {
+                        if name != "template" {
+                            spec.unknown_key(value, &format!("layout.{}", name));
+                            continue;
+                        }
+                        let template = string(value, name)?;
+                        spec.layout = Some(Layout::parse(template).map_err(|err| {
+                            invalid(value.line, "Invalid layout", &err.to_string())
+                        })?);
+                    }
+                }
+                _ => spec.unknown_key(item, key),
+            }
+        }
+        Ok(spec)
+    }
+
+    /// `Name = ["ext", ...]` or `[categories.Name]` with `extensions = [...]`
+    /// and an optional `destination`.
+    fn toml_categories(&mut self, categories: &Table) -> io::Result<()> {
+        for (name, item) in categories.iter() {
+            check_category_name(name, item.line, name)?;
+            let mut destination = None;
+            let extensions = match &item.value {
+                Value::Array(_) => strings(item, name)?,
+                Value::Table(options) => {
+                    let mut extensions = Vec::new();
+                    for (key, value) in options.iter() {
+                        match key {
+                            "extensions" => extensions = strings(value, key)?,
+                            "destination" => {
+                                destination =
+                                    Some(parse_destination(string(value, key)?, value.line)?);
+                            }
+                            _ => self.unknown_key(value, &format!("categories.{}.{}", name, key)),
+                        }
+                    }
+                    extensions
+                }
+                _ => return Err(type_error(item, name, "a list of extensions or a table")),
+            };
+            self.categories.push(CategorySpec {
+                name: name.to_string(),
+                extensions: extensions
+                    .into_iter()
+                    .filter_map(|(ext, line)| normalize_extension(&ext).map(|ext| (ext, line)))
+                    .collect(),
+                destination,
+            });
+        }
+        Ok(())
+    }
+
+    fn toml_rule(&mut self, item: &Item) -> io::Result<()> {
+        let rule = table(item, "rules")?;
+        let mut category = None;
+        let mut pattern = None;
+        let mut ignore_case = false;
+        let mut conditions = Vec::new();
+        let mut priority = 0;
+        for (key, value) in rule.iter() {
+            match key {
+                "category" => {
+                    let name = string(value, key)?;
+                    check_category_name(name, value.line, name)?;
+                    category = Some(name.to_string());
+                }
+                "name" => pattern = Some((string(value, key)?, value.line)),
+                "ignore_case" => ignore_case = boolean(value, key)?,
+                "size" | "modified" => {
+                    let text = format!("{} {}", key, string(value, key)?);
+                    let condition = Condition::parse_complete(&text)
+                        .map_err(|reason| invalid(value.line, "Invalid rule", &reason))?;
+                    conditions.push(condition);
+                }
+                "priority" => match value.value {
+                    Value::Integer(n) => priority = n,
+                    _ => return Err(type_error(value, key, "an integer")),
+                },
+                _ => self.unknown_key(value, &format!("rules.{}", key)),
+            }
+        }
+        if let Some((pattern, line)) = pattern {
+            let pattern = NamePattern::new(pattern, ignore_case)
+                .map_err(|reason| invalid(line, "Invalid rule", &reason))?;
+            conditions.insert(0, Condition::Name(pattern));
+        }
+        let category =
+            category.ok_or_else(|| invalid(item.line, "Invalid rule", "missing `category`"))?;
+        if conditions.is_empty() {
+            return Err(invalid(
+                item.line,
+                "Invalid rule",
+                "needs at least one of `name`, `size` or `modified`",
+            ));
+        }
+        self.rules.push(RuleSpec {
+            category,
+            conditions,
+            priority,
+            line: item.line,
+        });
+        Ok(())
+    }
+
+    fn unknown_key(&mut self, item: &Item, key: &str) {
+        self.unknown.push(Diagnostic {
+            line: item.line,
+            message: format!("unknown key `{}`", key),
+        });
+    }
+
+    fn build(self) -> Settings {
+        let mut rules = Rules::default();
+        let mut overlaps = Vec::new();
+        let mut listed: Vec<(Category, String, usize)> = Vec::new();
+        for spec in self.categories {
+            let category = rules.category(&spec.name);
+            if let Some(root) = spec.destination {
+                rules.set_destination(category.clone(), root);
+            }
+            for (ext, line) in spec.extensions {
+                let earlier = listed
+                    .iter()
+                    .find(|(c, e, _)| *c == category && *e == ext)
+                    .map(|(_, _, line)| *line);
+                if let Some(earlier) = earlier {
+                    overlaps.push(Diagnostic {
+                        line,
+                        message: format!(
+                            "extension `{}` is listed twice for `{}` (also on line {})",
+                            ext, category, earlier
+                        ),
+                    });
+                    continue;
+                }
+                if let Some(previous) = rules.insert(&category, ext.clone()) {
+                    overlaps.push(Diagnostic {
+                        line,
+                        message: format!(
+                            "extension `{}` moved from `{}` to `{}`",
+                            ext, previous, category
+                        ),
+                    });
+                }
+                listed.push((category.clone(), ext, line));
+            }
+        }
+        for spec in self.rules {
+            let category = rules.category(&spec.category);
+            rules.add_condition_rule(category, spec.conditions, spec.priority);
+        }
+        for glob in self.excludes {
+            rules.filter_mut().exclude(glob);
+        }
+        for glob in self.includes {
+            rules.filter_mut().include(glob);
+        }
+        Settings {
+            rules,
+            on_conflict: self.on_conflict,
+            layout: self.layout,
+            warnings: self.unknown,
+            overlaps,
+        }
+    }
+
+    /// A rule is unreachable when a rule tried before it needs only a subset
+    /// of its conditions, so it always matches first.
+    fn unreachable_rules(&self) -> Vec<Diagnostic> {
+        let mut ordered: Vec<&RuleSpec> = self.rules.iter().collect();
+        ordered.sort_by_key(|rule| std::cmp::Reverse(rule.priority));
+        let mut diagnostics = Vec::new();
+        for (idx, rule) in ordered.iter().enumerate() {
+            let shadow = ordered[..idx].iter().find(|earlier| {
+                earlier
+                    .conditions
+                    .iter()
+                    .all(|condition| rule.conditions.contains(condition))
+            });
+            if let Some(earlier) = shadow {
+                diagnostics.push(Diagnostic {
+                    line: rule.line,
+                    message: format!(
+                        "rule for `{}` can never match: the rule for `{}` on line {} always matches first",
+                        rule.category, earlier.category, earlier.line
+                    ),
+                });
+            }
+        }
+        diagnostics
+    }
+}
+
+fn normalize_extension(ext: &str) -> Option<String> {
+    let ext = ext.trim().trim_start_matches('.').to_ascii_lowercase();
+    (!ext.is_empty()).then_some(ext)
+}
+
+/// Category names become folders; `/` nests them (`Archive/Old`), but they
+/// must stay inside the destination.
+fn check_category_name(name: &str, line: usize, raw_line: &str) -> io::Result<()> {
+    let valid = !name.contains('\\')
+        && name
+            .split('/')
+            .all(|segment| !segment.trim().is_empty() && segment != "." && segment != "..");
+    if !valid {
+        return Err(invalid(line, "Invalid category name", raw_line));
+    }
+    Ok(())
+}
+
+/// A category's destination root; a leading `~` is the user's home folder.
+fn parse_destination(value: &str, line: usize) -> io::Result<PathBuf> {
+    let value = value.trim();
+    if value.is_empty() {
+        return Err(invalid(line, "Invalid destination", "empty path"));
+    }
+    let home = env::var_os("HOME").or_else(|| env::var_os("USERPROFILE"));
+    let rest = match value.strip_prefix('~') {
+        Some(rest) if rest.is_empty() || rest.starts_with(['/', '\\']) => rest,
+        _ => return Ok(PathBuf::from(value)),
+    };
+    let Some(home) = home else {
+        return Err(invalid(
+            line,
+            "Invalid destination",
+            "home folder is unknown",
+        ));
+    };
+    let rest = rest.trim_start_matches(['/', '\\']);
+    let mut path = PathBuf::from(home);
+    if !rest.is_empty() {
+        path.push(rest);
+    }
+    Ok(path)
+}
+
+/// Splits `Category: <condition>` into the category name and the condition.
+fn split_condition_rule(line: &str) -> Option<(&str, &str)> {
+    let (name, rule) = line.split_once(':')?;
+    Condition::is_condition(rule).then(|| (name.trim(), rule.trim()))
+}
+
+/// Parses `<condition> [priority <n>]`.
+fn parse_condition_rule(rule: &str) -> Result<(Condition, i64), String> {
+    let (condition, rest) = Condition::parse(rule)?;
+    let priority = match rest.trim() {
+        "" => 0,
+        rest => rest
+            .strip_prefix("priority")
+            .and_then(|n| n.trim().parse().ok())
+            .ok_or_else(|| format!("expected `priority <number>`, found `{}`", rest))?,
+    };
+    Ok((condition, priority))
+}
+
+fn table<'a>(item: &'a Item, key: &str) -> io::Result<&'a Table> {
+    match &item.value {
+        Value::Table(table) => Ok(table),
+        _ => Err(type_error(item, key, "a table")),
+    }
+}
+
+fn string<'a>(item: &'a Item, key: &str) -> io::Result<&'a str> {
+    match &item.value {
+        Value::String(value) => Ok(value),
+        _ => Err(type_error(item, key, "a string")),
+    }
+}
+
+fn boolean(item: &Item, key: &str) -> io::Result<bool> {
+    match item.value {
+        Value::Boolean(value) => Ok(value),
+        _ => Err(type_error(item, key, "true or false")),
+    }
+}
+
+/// An array of strings, each with its own line.
+fn strings(item: &Item, key: &str) -> io::Result<Vec<(String, usize)>> {
+    let Value::Array(items) = &item.value else {
+        return Err(type_error(item, key, "an array of strings"));
+    };
+    items
+        .iter()
+        .map(|element| Ok((string(element, key)?.to_string(), element.line)))
+        .collect()
+}
+
+fn type_error(item: &Item, key: &str, expected: &str) -> io::Error {
+    invalid(
+        item.line,
+        "Invalid config",
+        &format!(
+            "`{}` must be {}, found {}",
+            key,
+            expected,
+            item.value.type_name()
+        ),
+    )
+}
+
+fn invalid(line: usize, what: &str, detail: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidInput,
+        format!("{} on line {}: {}", what, line, detail),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_toml, Spec};
+    use crate::conflict::ConflictPolicy;
+    use crate::rules::Category;
+    use crate::toml;
+    use std::path::Path;
+
+    const EXAMPLE: &str = r#"
+[categories]
+Fonts = ["ttf", ".OTF"]
+
+[categories."Archive/Old"]
+extensions = ["bak"]
+colour = "grey"
+destination = "/mnt/cold"
+
+[[rules]]
+category = "Invoices"
+name = '^INV-\d+'
+ignore_case = true
+priority = 5
+
+[ignore]
+exclude = ["*.part", ".git/**"]
+
+[conflict]
+policy = "rename"
+
+[layout]
+template = "{category}/{year}/{name}"
+"#;
+
+    #[test]
+    fn loads_toml() {
+        let settings = parse_toml(EXAMPLE).unwrap();
+        let rules = &settings.rules;
+        assert_eq!(
+            rules.classify(Path::new("font.otf")),
+            Category::new("Fonts")
+        );
+        assert_eq!(
+            rules.classify(Path::new("db.bak")),
+            Category::new("Archive/Old")
+        );
+        assert_eq!(
+            rules.classify(Path::new("inv-7.pdf")),
+            Category::new("Invoices")
+        );
+        assert!(!rules
+            .filter()
+            .allows_file(Path::new("in"), Path::new("in/a.part")));
+        assert_eq!(settings.on_conflict, Some(ConflictPolicy::Rename));
+        assert_eq!(
+            settings.layout.unwrap().to_string(),
+            "{category}/{year}/{name}"
+        );
+        assert_eq!(
+            rules.destination(Path::new("out"), &Category::new("Archive/Old")),
+            Path::new("/mnt/cold")
+        );
+        assert_eq!(settings.warnings.len(), 1);
+        assert_eq!(
+            settings.warnings[0].to_string(),
+            "line 7: unknown key `categories.Archive/Old.colour`"
+        );
+    }
+
+    #[test]
+    fn reports_overlaps_and_unreachable_rules() {
+        let spec = Spec::from_toml(
+            &toml::parse(
+                "[categories]\n\
+                 Code = ['rs', 'py', 'md']\n\
+                 Scripts = ['py', 'py']\n\
+                 [[rules]]\n\
+                 category = 'Big'\n\
+                 size = '> 1GB'\n\
+                 [[rules]]\n\
+                 category = 'BigIso'\n\
+                 size = '> 1GB'\n\
+                 name = 'iso$'\n",
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        let unreachable = spec.unreachable_rules();
+        assert_eq!(unreachable.len(), 1);
+        assert_eq!(unreachable[0].line, 7);
+
+        let messages: Vec<String> = spec
+            .build()
+            .overlaps
+            .iter()
+            .map(|overlap| overlap.to_string())
+            .collect();
+        assert_eq!(
+            messages,
+            [
+                "line 2: extension `md` moved from `Documents` to `Code`",
+                "line 3: extension `py` moved from `Code` to `Scripts`",
+                "line 3: extension `py` is listed twice for `Scripts` (also on line 3)",
+            ]
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_toml_values() {
+        assert!(parse_toml("[conflict]\npolicy = 'sometimes'\n").is_err());
+        assert!(parse_toml("[[rules]]\ncategory = 'X'\n").is_err());
+        assert!(parse_toml("[categories]\nFonts = 'ttf'\n").is_err());
+        let Err(err) = parse_toml("[[rules]]\ncategory = 'X'\npriority = 'high'\n") else {
+            panic!("a string priority was accepted");
+        };
+        assert!(err.to_string().contains("line 3"), "{}", err);
+    }
+}
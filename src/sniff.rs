@@ -0,0 +1,197 @@
+//! Content type detection from a file's leading bytes.
+
+use std::fs;
+use std::io;
+use std::io::Read;
+use std::path::Path;
+
+/// How many leading bytes are read to recognise a file.
+const SNIFF_LEN: usize = 4096;
+
+/// Extensions that are spelled differently but describe the same content.
+const EQUIVALENT: &[&[&str]] = &[
+    &["jpg", "jpeg", "jpe", "jfif"],
+    &["mp4", "m4v"],
+    &["gz", "tgz"],
+    &["mkv", "mka", "mk3d"],
+    &["heic", "heif"],
+    // Office Open XML: macro-enabled files and templates share the layout.
+    &["docx", "docm", "dotx", "dotm"],
+    &["xlsx", "xlsm", "xltx", "xltm", "xlsb", "xlam"],
+    &["pptx", "pptm", "potx", "potm", "ppsx", "ppsm", "ppam"],
+];
+
+/// Containers that many formats are built on (epub, jar, odt, ...). Any
+/// extension is trusted over these.
+const GENERIC: &[&str] = &["zip"];
+
+/// Reads the start of `path` and returns the canonical extension of a
+/// recognised signature.
+pub fn detect(path: &Path) -> io::Result<Option<&'static str>> {
+    let mut buf = Vec::with_capacity(SNIFF_LEN);
+    fs::File::open(path)?
+        .take(SNIFF_LEN as u64)
+        .read_to_end(&mut buf)?;
+    Ok(detect_bytes(&buf))
+}
+
+pub fn detect_bytes(bytes: &[u8]) -> Option<&'static str> {
+    if bytes.starts_with(&[0xFF, 0xD8, 0xFF]) {
+        Some("jpg")
+    } else if bytes.starts_with(b"\x89PNG\r\n\x1a\n") {
+        Some("png")
+    } else if bytes.starts_with(b"GIF87a") || bytes.starts_with(b"GIF89a") {
+        Some("gif")
+    } else if bytes.starts_with(b"%PDF-") {
+        Some("pdf")
+    } else if bytes.starts_with(b"PK\x03\x04") || bytes.starts_with(b"PK\x05\x06") {
+        Some(zip_flavour(bytes))
+    } else if bytes.starts_with(&[0x1F, 0x8B]) {
+        Some("gz")
+    } else if bytes.starts_with(&[0x37, 0x7A, 0xBC, 0xAF, 0x27, 0x1C]) {
+        Some("7z")
+    } else if bytes.starts_with(b"fLaC") {
+        Some("flac")
+    } else if bytes.starts_with(&[0x1A, 0x45, 0xDF, 0xA3]) {
+        if contains(bytes, b"webm") {
+            Some("webm")
+        } else {
+            Some("mkv")
+        }
+    } else if bytes.starts_with(b"RIFF") && bytes.len() >= 12 {
+        match &bytes[8..12] {
+            b"WAVE" => Some("wav"),
+            b"AVI " => Some("avi"),
+            b"WEBP" => Some("webp"),
+            _ => None,
+        }
+    } else if bytes.len() >= 12 && &bytes[4..8] == b"ftyp" {
+        ftyp_flavour(bytes)
+    } else if bytes.starts_with(b"ID3") || is_mpeg_audio_frame(bytes) {
+        Some("mp3")
+    } else {
+        None
+    }
+}
+
+/// Whether `ext` (from the file name) and the detected type disagree.
+pub fn is_mismatch(ext: Option<&str>, detected: &str) -> bool {
+    let Some(ext) = ext else {
+        return true;
+    };
+    let ext = ext.to_ascii_lowercase();
+    if ext == detected {
+        return false;
+    }
+    !EQUIVALENT
+        .iter()
+        .any(|group| group.contains(&ext.as_str()) && group.contains(&detected))
+}
+
+pub fn is_generic(detected: &str) -> bool {
+    GENERIC.contains(&detected)
+}
+
+fn zip_flavour(bytes: &[u8]) -> &'static str {
+    if contains(bytes, b"word/") {
+        "docx"
+    } else if contains(bytes, b"xl/") {
+        "xlsx"
+    } else if contains(bytes, b"ppt/") {
+        "pptx"
+    } else {
+        "zip"
+    }
+}
+
+/// Reads the major brand of an ISO media `ftyp` box. HEIF images and
+/// unknown brands are not guessed as video.
+fn ftyp_flavour(bytes: &[u8]) -> Option<&'static str> {
+    match &bytes[8..12] {
+        b"qt  " => Some("mov"),
+        b"M4A " | b"M4B " => Some("m4a"),
+        b"avif" | b"avis" => Some("avif"),
+        b"heic" | b"heix" | b"heim" | b"heis" | b"hevc" | b"hevx" => Some("heic"),
+        // Generic HEIF: the compatible brands tell AVIF and HEIC apart.
+        b"mif1" | b"msf1" => Some(if compatible_brands(bytes).any(|b| b == b"avif") {
+            "avif"
+        } else {
+            "heic"
+        }),
+        b"3gp4" | b"3gp5" | b"3gp6" | b"3g2a" => Some("3gp"),
+        b"isom" | b"iso2" | b"iso4" | b"iso5" | b"iso6" | b"mp41" | b"mp42" | b"avc1" | b"dash"
+        | b"M4V " | b"M4VH" | b"M4VP" | b"f4v " | b"MSNV" => Some("mp4"),
+        _ => None,
+    }
+}
+
+/// The brands listed after the major brand and minor version of a `ftyp` box.
+fn compatible_brands(bytes: &[u8]) -> impl Iterator<Item = &[u8]> {
+    let size = bytes.get(..4).map_or(0, |len| {
+        u32::from_be_bytes([len[0], len[1], len[2], len[3]]) as usize
+    });
+    let end = size.min(bytes.len());
+    bytes.get(16..end).unwrap_or(&[]).chunks_exact(4)
+}
+
+fn is_mpeg_audio_frame(bytes: &[u8]) -> bool {
+    bytes.len() >= 2 && bytes[0] == 0xFF && (bytes[1] & 0xE6) == 0xE2
+}
+
+fn contains(haystack: &[u8], needle: &[u8]) -> bool {
+    haystack.windows(needle.len()).any(|w| w == needle)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{detect_bytes, is_mismatch};
+
+    #[test]
+    fn detects_common_signatures() {
+        assert_eq!(detect_bytes(b"\xFF\xD8\xFF\xE0\x00\x10JFIF"), Some("jpg"));
+        assert_eq!(detect_bytes(b"\x89PNG\r\n\x1a\n\x00\x00"), Some("png"));
+        assert_eq!(detect_bytes(b"%PDF-1.7\n"), Some("pdf"));
+        assert_eq!(
+            detect_bytes(b"PK\x03\x04\x14\x00word/document.xml"),
+            Some("docx")
+        );
+        assert_eq!(detect_bytes(b"RIFF\x24\x00\x00\x00WAVEfmt "), Some("wav"));
+        assert_eq!(
+            detect_bytes(b"\x00\x00\x00\x18ftypisom\x00\x00"),
+            Some("mp4")
+        );
+        assert_eq!(
+            detect_bytes(b"\x00\x00\x00\x18ftypheic\x00\x00\x00\x00"),
+            Some("heic")
+        );
+        assert_eq!(
+            detect_bytes(b"\x00\x00\x00\x1cftypavif\x00\x00\x00\x00"),
+            Some("avif")
+        );
+        assert_eq!(
+            detect_bytes(b"\x00\x00\x00\x18ftypmif1\x00\x00\x00\x00mif1avif"),
+            Some("avif")
+        );
+        assert_eq!(
+            detect_bytes(b"\x00\x00\x00\x14ftypmif1\x00\x00\x00\x00heic"),
+            Some("heic")
+        );
+        assert_eq!(
+            detect_bytes(b"\x00\x00\x00\x14ftypcrx \x00\x00\x00\x00"),
+            None
+        );
+        assert_eq!(detect_bytes(b"ID3\x04\x00"), Some("mp3"));
+        assert_eq!(detect_bytes(b"plain text"), None);
+    }
+
+    #[test]
+    fn mismatch_respects_equivalent_extensions() {
+        assert!(!is_mismatch(Some("JPEG"), "jpg"));
+        assert!(is_mismatch(Some("jpg"), "png"));
+        assert!(is_mismatch(None, "pdf"));
+        assert!(!is_mismatch(Some("xlsm"), "xlsx"));
+        assert!(!is_mismatch(Some("docm"), "docx"));
+        assert!(!is_mismatch(Some("potx"), "pptx"));
+        assert!(is_mismatch(Some("docm"), "xlsx"));
+    }
+}
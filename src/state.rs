@@ -0,0 +1,72 @@
+//! The per-folder state file used to report changes between runs.
+
+use std::collections::HashSet;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Name of the change-tracking state file every run writes in the source folder.
+pub const STATE_FILE_NAME: &str = ".rusty-sort-state.txt";
+
+/// Counts `(added, removed)` paths between two scans.
+pub fn diff_files(before: &[PathBuf], after: &[PathBuf]) -> (usize, usize) {
+    let before_set: HashSet<&PathBuf> = before.iter().collect();
+    let after_set: HashSet<&PathBuf> = after.iter().collect();
+
+    let added = after_set.difference(&before_set).count();
+    let removed = before_set.difference(&after_set).count();
+
+    (added, removed)
+}
+
+/// Location of the state file inside a scanned folder.
+pub fn state_path(base_dir: &Path) -> PathBuf {
+    base_dir.join(STATE_FILE_NAME)
+}
+
+/// Loads the file list recorded by the previous run, as paths under `base_dir`.
+pub fn load(base_dir: &Path) -> io::Result<Vec<PathBuf>> {
+    let path = state_path(base_dir);
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+
+    let content = fs::read_to_string(path)?;
+    let mut files = Vec::new();
+    for line in content.lines() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        files.push(base_dir.join(line));
+    }
+    Ok(files)
+}
+
+/// Records `files` (relative to `base_dir`) for the next run to compare against.
+pub fn save(base_dir: &Path, files: &[PathBuf]) -> io::Result<()> {
+    let mut lines = String::new();
+    for file in files {
+        if let Ok(rel) = file.strip_prefix(base_dir) {
+            lines.push_str(&rel.to_string_lossy());
+            lines.push('\n');
+        }
+    }
+    fs::write(state_path(base_dir), lines)
+}
+
+/// Counts `(added, removed)` files between the recorded state and a new scan.
+pub fn diff(previous: &[PathBuf], current: &[PathBuf], base_dir: &Path) -> (usize, usize) {
+    let prev_rel: HashSet<PathBuf> = previous
+        .iter()
+        .filter_map(|p| p.strip_prefix(base_dir).ok().map(|r| r.to_path_buf()))
+        .collect();
+    let curr_rel: HashSet<PathBuf> = current
+        .iter()
+        .filter_map(|p| p.strip_prefix(base_dir).ok().map(|r| r.to_path_buf()))
+        .collect();
+
+    let added = curr_rel.difference(&prev_rel).count();
+    let removed = prev_rel.difference(&curr_rel).count();
+
+    (added, removed)
+}
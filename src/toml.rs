@@ -0,0 +1,208 @@
+//! Reads rules files with the `toml` crate and keeps the line each value was
+//! defined on, so configuration problems can be reported precisely.
+
+use std::io;
+
+use ::toml::de::{DeTable, DeValue};
+use ::toml::Spanned;
+
+/// A value together with the line it starts on (1-based).
+#[derive(Debug, Clone, PartialEq)]
+pub struct Item {
+    pub value: Value,
+    pub line: usize,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    String(String),
+    Integer(i64),
+    Float(f64),
+    Boolean(bool),
+    /// Dates and times, kept as written.
+    Datetime(String),
+    Array(Vec<Item>),
+    Table(Table),
+}
+
+/// Keys in the order they were defined.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Table {
+    entries: Vec<(String, Item)>,
+}
+
+impl Table {
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &Item)> {
+        self.entries.iter().map(|(key, item)| (key.as_str(), item))
+    }
+}
+
+impl Value {
+    /// Name used in type errors, e.g. "expected a string, found an array".
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Value::String(_) => "a string",
+            Value::Integer(_) => "an integer",
+            Value::Float(_) => "a float",
+            Value::Boolean(_) => "a boolean",
+            Value::Datetime(_) => "a date",
+            Value::Array(_) => "an array",
+            Value::Table(_) => "a table",
+        }
+    }
+}
+
+/// Parses a whole document into its root table.
+pub fn parse(content: &str) -> io::Result<Table> {
+    let root = DeTable::parse(content).map_err(|err| {
+        let line = err.span().map_or(1, |span| line_at(content, span.start));
+        invalid(line, err.message())
+    })?;
+    convert_table(root.get_ref(), content)
+}
+
+fn convert_table(table: &DeTable<'_>, content: &str) -> io::Result<Table> {
+    let mut entries = Vec::new();
+    for (key, value) in table.iter() {
+        // Point at the key: that is where a table or value is introduced.
+        let item = convert(value, line_at(content, key.span().start), content)?;
+        entries.push((key.get_ref().to_string(), item));
+    }
+    Ok(Table { entries })
+}
+
+fn convert(value: &Spanned<DeValue<'_>>, line: usize, content: &str) -> io::Result<Item> {
+    let value = match value.get_ref() {
+        DeValue::String(text) => Value::String(text.to_string()),
+        DeValue::Integer(integer) => Value::Integer(
+            i64::from_str_radix(integer.as_str(), integer.radix())
+                .map_err(|_| invalid(line, "integer out of range"))?,
+        ),
+        DeValue::Float(float) => Value::Float(
+            float
+                .as_str()
+                .parse()
+                .map_err(|_| invalid(line, "invalid float"))?,
+        ),
+        DeValue::Boolean(flag) => Value::Boolean(*flag),
+        DeValue::Datetime(datetime) => Value::Datetime(datetime.to_string()),
+        DeValue::Array(array) => Value::Array(
+            array
+                .iter()
+                .map(|element| convert(element, line_at(content, element.span().start), content))
+                .collect::<io::Result<_>>()?,
+        ),
+        DeValue::Table(table) => Value::Table(convert_table(table, content)?),
+    };
+    Ok(Item { value, line })
+}
+
+/// 1-based line of the byte `offset` in `content`.
+fn line_at(content: &str, offset: usize) -> usize {
+    content[..offset.min(content.len())]
+        .bytes()
+        .filter(|&b| b == b'\n')
+        .count()
+        + 1
+}
+
+fn invalid(line: usize, reason: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidInput,
+        format!("Invalid TOML on line {}: {}", line, reason.trim_end()),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse, Item, Table, Value};
+
+    fn get<'a>(table: &'a Table, key: &str) -> Option<&'a Item> {
+        table
+            .iter()
+            .find(|(name, _)| *name == key)
+            .map(|(_, item)| item)
+    }
+
+    #[test]
+    fn parses_tables_arrays_and_lines() {
+        let doc = parse(
+            "# rules\n\
+             [categories]\n\
+             Images = ['jpg', \"png\"] # trailing\n\
+             \"Archive/Old\" = { extensions = [\n  'bak',\n  'old',\n] }\n\
+             \n\
+             [[rules]]\n\
+             category = 'Invoices'\n\
+             name = '^INV-\\d+'\n\
+             priority = -2\n\
+             [[rules]]\n\
+             category = \"Large\"\n\
+             enabled = true\n",
+        )
+        .unwrap();
+
+        let categories = get(&doc, "categories").unwrap();
+        assert_eq!(categories.line, 2);
+        let Value::Table(categories) = &categories.value else {
+            panic!("categories is not a table");
+        };
+        let old = get(categories, "Archive/Old").unwrap();
+        assert_eq!(old.line, 4);
+        let Value::Table(old) = &old.value else {
+            panic!("inline table expected");
+        };
+        let Value::Array(extensions) = &get(old, "extensions").unwrap().value else {
+            panic!("array expected");
+        };
+        assert_eq!(extensions[1].line, 6);
+
+        let Value::Array(rules) = &get(&doc, "rules").unwrap().value else {
+            panic!("rules is not an array");
+        };
+        assert_eq!(rules.len(), 2);
+        let Value::Table(first) = &rules[0].value else {
+            panic!("rule is not a table");
+        };
+        assert_eq!(
+            get(first, "name").unwrap().value,
+            Value::String("^INV-\\d+".to_string())
+        );
+        assert_eq!(get(first, "priority").unwrap().value, Value::Integer(-2));
+    }
+
+    #[test]
+    fn reports_errors_with_line_numbers() {
+        let err = parse("[a]\nx = 1\nx = 2\n").unwrap_err();
+        assert!(err.to_string().contains("line 3"), "{}", err);
+        let err = parse("[a]\n[a]\n").unwrap_err();
+        assert!(err.to_string().contains("line 2"), "{}", err);
+        assert!(parse("x = \"unterminated\n").is_err());
+        assert!(parse("x = bare\n").is_err());
+        assert!(parse("x = 1 y = 2\n").is_err());
+    }
+
+    #[test]
+    fn accepts_the_rest_of_toml() {
+        let doc = parse(
+            "ratio = 1.5\n\
+             since = 2024-01-01\n\
+             note = \"\"\"\nmulti\nline\"\"\"\n\
+             mask = 0o755\n\
+             [nested]\n\
+             inner = { deep = { flag = false } }\n",
+        )
+        .unwrap();
+        assert_eq!(get(&doc, "ratio").unwrap().value, Value::Float(1.5));
+        assert_eq!(
+            get(&doc, "since").unwrap().value,
+            Value::Datetime("2024-01-01".to_string())
+        );
+        assert_eq!(
+            get(&doc, "note").unwrap().value,
+            Value::String("multi\nline".to_string())
+        );
+        assert_eq!(get(&doc, "mask").unwrap().value, Value::Integer(0o755));
+        assert_eq!(get(&doc, "nested").unwrap().line, 7);
+    }
+}
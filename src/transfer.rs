@@ -0,0 +1,306 @@
+//! Moving, copying or linking a single file, including across filesystems.
+
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::conflict::files_identical;
+
+/// What sorting does with the original file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Mode {
+    /// Move the original into place.
+    #[default]
+    Move,
+    /// Leave the original alone and write a verified copy.
+    Copy,
+    /// Leave the original alone and hard link it into place, or symlink it
+    /// when the target is on another filesystem.
+    Link,
+}
+
+impl Mode {
+    /// Parses `move`, `copy` or `link`.
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "move" => Some(Mode::Move),
+            "copy" => Some(Mode::Copy),
+            "link" => Some(Mode::Link),
+            _ => None,
+        }
+    }
+
+    /// "Moved", "Copied" or "Linked", for summaries.
+    pub fn past_tense(self) -> &'static str {
+        match self {
+            Mode::Move => "Moved",
+            Mode::Copy => "Copied",
+            Mode::Link => "Linked",
+        }
+    }
+}
+
+impl fmt::Display for Mode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Mode::Move => "move",
+            Mode::Copy => "copy",
+            Mode::Link => "link",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// How `transfer` or `move_file` put a file into place.
+pub enum Transfer {
+    /// The file was renamed on the same filesystem.
+    Renamed,
+    /// The target is on another filesystem; the file was copied, verified and the source removed.
+    Copied,
+    /// A verified copy was written and the source kept (`Mode::Copy`).
+    Duplicated,
+    /// The target is a hard link to the source (`Mode::Link`).
+    HardLinked,
+    /// Hard links cannot cross filesystems, so a symlink to the source was made.
+    Symlinked,
+}
+
+/// Puts `source` at `target` as `mode` asks, replacing any existing target.
+pub fn transfer(mode: Mode, source: &Path, target: &Path) -> io::Result<Transfer> {
+    match mode {
+        Mode::Move => move_file(source, target),
+        Mode::Copy => {
+            copy_into_place(source, target)?;
+            Ok(Transfer::Duplicated)
+        }
+        Mode::Link => link_file(source, target),
+    }
+}
+
+/// Moves `source` to `target`, falling back to copy-verify-delete across filesystems.
+pub fn move_file(source: &Path, target: &Path) -> io::Result<Transfer> {
+    match fs::rename(source, target) {
+        Ok(()) => Ok(Transfer::Renamed),
+        Err(err) if err.kind() == io::ErrorKind::CrossesDevices => move_across(source, target),
+        Err(err) => Err(err),
+    }
+}
+
+/// The cross-filesystem half of `move_file`: copy, verify, then delete the source.
+fn move_across(source: &Path, target: &Path) -> io::Result<Transfer> {
+    copy_into_place(source, target)?;
+    fs::remove_file(source)?;
+    Ok(Transfer::Copied)
+}
+
+/// Copies to a temporary file next to `target` and renames it into place, so
+/// a half-written copy never shows up under the real name.
+fn copy_into_place(source: &Path, target: &Path) -> io::Result<()> {
+    let temp = temp_path(target);
+    if let Err(err) = copy_verified(source, &temp) {
+        let _ = fs::remove_file(&temp);
+        return Err(err);
+    }
+    fs::rename(&temp, target)
+}
+
+fn link_file(source: &Path, target: &Path) -> io::Result<Transfer> {
+    let temp = temp_path(target);
+    let transfer = match fs::hard_link(source, &temp) {
+        Ok(()) => Transfer::HardLinked,
+        Err(err) if err.kind() == io::ErrorKind::CrossesDevices => {
+            symlink(&std::path::absolute(source)?, &temp)?;
+            Transfer::Symlinked
+        }
+        Err(err) => return Err(err),
+    };
+    if let Err(err) = fs::rename(&temp, target) {
+        let _ = fs::remove_file(&temp);
+        return Err(err);
+    }
+    // Renaming onto another link to the same file is a no-op that leaves the
+    // temporary link behind.
+    if fs::symlink_metadata(&temp).is_ok() {
+        fs::remove_file(&temp)?;
+    }
+    Ok(transfer)
+}
+
+#[cfg(unix)]
+fn symlink(original: &Path, link: &Path) -> io::Result<()> {
+    std::os::unix::fs::symlink(original, link)
+}
+
+#[cfg(windows)]
+fn symlink(original: &Path, link: &Path) -> io::Result<()> {
+    std::os::windows::fs::symlink_file(original, link)
+}
+
+/// Copies into `temp` (same directory as the target) and checks it matches the source.
+///
+/// The modification time and permissions are set through the handle used for
+/// writing, so a read-only source does not lock us out of our own copy.
+fn copy_verified(source: &Path, temp: &Path) -> io::Result<()> {
+    let metadata = fs::metadata(source)?;
+    let mut reader = fs::File::open(source)?;
+    let mut writer = fs::File::create(temp)?;
+    io::copy(&mut reader, &mut writer)?;
+    writer.set_modified(metadata.modified()?)?;
+    writer.set_permissions(metadata.permissions())?;
+    drop(writer);
+
+    verify_copy(source, temp)
+}
+
+fn verify_copy(source: &Path, temp: &Path) -> io::Result<()> {
+    if !files_identical(source, temp)? {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("Copy of {} does not match the original", source.display()),
+        ));
+    }
+    Ok(())
+}
+
+fn temp_path(target: &Path) -> PathBuf {
+    let name = target
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    target.with_file_name(format!(".{}.rusty-sort-tmp", name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{move_across, transfer, verify_copy, Mode, Transfer};
+    use std::env;
+    use std::fs;
+    use std::path::PathBuf;
+    use std::time::{Duration, SystemTime};
+
+    fn scratch(name: &str) -> PathBuf {
+        let dir = env::temp_dir().join(format!("rusty-sort-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn cross_device_fallback_moves_and_keeps_mtime() {
+        let dir = scratch("fallback");
+        let source = dir.join("a.txt");
+        fs::write(&source, "original").unwrap();
+        let mtime = SystemTime::UNIX_EPOCH + Duration::from_secs(1_600_000_000);
+        fs::File::options()
+            .write(true)
+            .open(&source)
+            .unwrap()
+            .set_modified(mtime)
+            .unwrap();
+
+        let target = dir.join("b.txt");
+        assert!(matches!(
+            move_across(&source, &target).unwrap(),
+            Transfer::Copied
+        ));
+        assert!(!source.exists());
+        assert_eq!(fs::read_to_string(&target).unwrap(), "original");
+        assert_eq!(fs::metadata(&target).unwrap().modified().unwrap(), mtime);
+        // Only the target is left; no temporary file behind it.
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn cross_device_fallback_keeps_read_only_mode() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = scratch("fallback-mode");
+        let source = dir.join("a.txt");
+        fs::write(&source, "original").unwrap();
+        fs::set_permissions(&source, fs::Permissions::from_mode(0o444)).unwrap();
+
+        let target = dir.join("b.txt");
+        move_across(&source, &target).unwrap();
+        let mode = fs::metadata(&target).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o444);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn copy_mode_handles_a_read_only_source() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = scratch("copy-read-only");
+        let source = dir.join("a.txt");
+        fs::write(&source, "archived").unwrap();
+        fs::set_permissions(&source, fs::Permissions::from_mode(0o444)).unwrap();
+
+        let copy = dir.join("copy.txt");
+        assert!(matches!(
+            transfer(Mode::Copy, &source, &copy).unwrap(),
+            Transfer::Duplicated
+        ));
+        assert_eq!(fs::read_to_string(&source).unwrap(), "archived");
+        assert_eq!(fs::read_to_string(&copy).unwrap(), "archived");
+        let mode = fs::metadata(&copy).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o444);
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 2);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn verification_rejects_a_different_copy() {
+        let dir = scratch("verify");
+        let source = dir.join("a.txt");
+        let copy = dir.join("b.txt");
+        fs::write(&source, "original").unwrap();
+
+        fs::write(&copy, "original").unwrap();
+        verify_copy(&source, &copy).unwrap();
+        // Same size, different bytes.
+        fs::write(&copy, "0riginal").unwrap();
+        assert!(verify_copy(&source, &copy).is_err());
+        fs::write(&copy, "short").unwrap();
+        assert!(verify_copy(&source, &copy).is_err());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn copy_and_link_keep_the_source() {
+        let dir = env::temp_dir().join(format!("rusty-sort-transfer-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let source = dir.join("a.txt");
+        fs::write(&source, "original").unwrap();
+
+        let copy = dir.join("copy.txt");
+        assert!(matches!(
+            transfer(Mode::Copy, &source, &copy).unwrap(),
+            Transfer::Duplicated
+        ));
+        let link = dir.join("link.txt");
+        assert!(matches!(
+            transfer(Mode::Link, &source, &link).unwrap(),
+            Transfer::HardLinked
+        ));
+        // Linking again over an existing link to the same file is harmless.
+        transfer(Mode::Link, &source, &link).unwrap();
+
+        assert_eq!(fs::read_to_string(&source).unwrap(), "original");
+        assert_eq!(fs::read_to_string(&copy).unwrap(), "original");
+        assert_eq!(fs::read_to_string(&link).unwrap(), "original");
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 3);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn parses_modes() {
+        assert_eq!(Mode::parse("Copy"), Some(Mode::Copy));
+        assert_eq!(Mode::parse("hardlink"), None);
+        assert_eq!(Mode::default().to_string(), "move");
+    }
+}
@@ -0,0 +1,295 @@
+//! Change tracking for `watch` mode.
+
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+/// How long a file's size must stay unchanged before it is considered complete.
+const STABLE_FOR: Duration = Duration::from_secs(2);
+/// How often pending files are re-checked while waiting for them to settle.
+const SETTLE_TICK: Duration = Duration::from_millis(500);
+/// Upper bound between rescans when no change notification arrives.
+const IDLE_TICK: Duration = Duration::from_secs(5);
+/// Rescan interval when change notifications are unavailable.
+const POLL_TICK: Duration = Duration::from_secs(1);
+
+/// Tracks files in a watched directory and reports the ones that have
+/// appeared since the last check and stopped growing.
+pub struct Tracker {
+    known: HashSet<PathBuf>,
+    pending: HashMap<PathBuf, (u64, Instant)>,
+}
+
+impl Tracker {
+    /// Files already present when watching starts are left alone.
+    pub fn new(existing: &[PathBuf]) -> Self {
+        Self {
+            known: existing.iter().cloned().collect(),
+            pending: HashMap::new(),
+        }
+    }
+
+    /// Feeds the current listing and returns files that are new and settled.
+    pub fn update(&mut self, files: &[PathBuf]) -> Vec<PathBuf> {
+        let sized: Vec<(PathBuf, Option<u64>)> = files
+            .iter()
+            .map(|file| (file.clone(), fs::metadata(file).map(|m| m.len()).ok()))
+            .collect();
+        self.observe(&sized, Instant::now())
+    }
+
+    /// `update` with the sizes already read (`None` when unreadable) and the
+    /// time of the listing given explicitly.
+    fn observe(&mut self, files: &[(PathBuf, Option<u64>)], now: Instant) -> Vec<PathBuf> {
+        let present: HashSet<&PathBuf> = files.iter().map(|(path, _)| path).collect();
+        self.known.retain(|path| present.contains(path));
+        self.pending.retain(|path, _| present.contains(path));
+
+        let mut ready = Vec::new();
+        for (file, size) in files {
+            if self.known.contains(file) {
+                continue;
+            }
+            let Some(size) = *size else {
+                continue;
+            };
+            match self.pending.get(file) {
+                Some(&(last_size, since)) if last_size == size => {
+                    if now.duration_since(since) >= STABLE_FOR {
+                        self.pending.remove(file);
+                        ready.push(file.clone());
+                    }
+                }
+                _ => {
+                    self.pending.insert(file.clone(), (size, now));
+                }
+            }
+        }
+        ready
+    }
+
+    /// Marks paths as handled so they are not picked up again.
+    pub fn mark_known(&mut self, paths: impl IntoIterator<Item = PathBuf>) {
+        self.known.extend(paths);
+    }
+
+    /// Whether any file is still waiting for its size to settle.
+    pub fn has_pending(&self) -> bool {
+        !self.pending.is_empty()
+    }
+}
+
+/// Blocks until something changes in the watched tree or a tick elapses.
+pub struct Waiter {
+    #[cfg(target_os = "linux")]
+    inotify: Option<inotify::Inotify>,
+}
+
+impl Waiter {
+    /// Watches `dir` (and its subfolders if `recursive`), falling back to polling.
+    pub fn new(dir: &Path, recursive: bool) -> Self {
+        #[cfg(target_os = "linux")]
+        {
+            let inotify = inotify::Inotify::new().ok();
+            let mut waiter = Self { inotify };
+            waiter.watch_tree(dir, recursive);
+            waiter
+        }
+        #[cfg(not(target_os = "linux"))]
+        {
+            let _ = (dir, recursive);
+            Self {}
+        }
+    }
+
+    /// Re-registers directories so folders created since the last call are
+    /// watched too.
+    pub fn watch_tree(&mut self, dir: &Path, recursive: bool) {
+        #[cfg(target_os = "linux")]
+        if let Some(inotify) = self.inotify.as_mut() {
+            let _ = inotify.add_watch(dir);
+            if recursive {
+                for sub in list_dirs(dir) {
+                    let _ = inotify.add_watch(&sub);
+                }
+            }
+        }
+        #[cfg(not(target_os = "linux"))]
+        let _ = (dir, recursive);
+    }
+
+    /// Sleeps until a change is seen or the next tick, which is shorter while files are settling.
+    pub fn wait(&mut self, settling: bool) {
+        let timeout = if settling { SETTLE_TICK } else { IDLE_TICK };
+
+        #[cfg(target_os = "linux")]
+        if let Some(inotify) = self.inotify.as_mut() {
+            if inotify.wait(timeout).is_ok() {
+                return;
+            }
+        }
+
+        std::thread::sleep(if settling { SETTLE_TICK } else { POLL_TICK });
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn list_dirs(dir: &Path) -> Vec<PathBuf> {
+    let mut dirs = Vec::new();
+    let mut stack = vec![dir.to_path_buf()];
+    while let Some(current) = stack.pop() {
+        let Ok(entries) = fs::read_dir(&current) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            if entry.file_type().map(|t| t.is_dir()).unwrap_or(false) {
+                dirs.push(entry.path());
+                stack.push(entry.path());
+            }
+        }
+    }
+    dirs
+}
+
+#[cfg(target_os = "linux")]
+mod inotify {
+    use std::ffi::CString;
+    use std::fs::File;
+    use std::io;
+    use std::io::Read;
+    use std::os::fd::{AsRawFd, FromRawFd};
+    use std::os::unix::ffi::OsStrExt;
+    use std::path::Path;
+    use std::time::Duration;
+
+    pub struct Inotify {
+        file: File,
+    }
+
+    impl Inotify {
+        pub fn new() -> io::Result<Self> {
+            // SAFETY: plain syscall wrapper; the returned descriptor is owned by `file`.
+            let fd = unsafe { libc::inotify_init1(libc::IN_NONBLOCK | libc::IN_CLOEXEC) };
+            if fd < 0 {
+                return Err(io::Error::last_os_error());
+            }
+            // SAFETY: `fd` is a freshly opened descriptor nobody else owns.
+            let file = unsafe { File::from_raw_fd(fd) };
+            Ok(Self { file })
+        }
+
+        pub fn add_watch(&mut self, path: &Path) -> io::Result<()> {
+            let path = CString::new(path.as_os_str().as_bytes())
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+            let mask = libc::IN_MODIFY | libc::IN_CLOSE_WRITE | libc::IN_MOVED_TO | libc::IN_CREATE;
+            // SAFETY: `path` is a valid NUL-terminated string for the duration of the call.
+            let wd = unsafe { libc::inotify_add_watch(self.file.as_raw_fd(), path.as_ptr(), mask) };
+            if wd < 0 {
+                return Err(io::Error::last_os_error());
+            }
+            Ok(())
+        }
+
+        /// Waits for events (or the timeout) and drains whatever was queued.
+        pub fn wait(&mut self, timeout: Duration) -> io::Result<()> {
+            let mut pfd = libc::pollfd {
+                fd: self.file.as_raw_fd(),
+                events: libc::POLLIN,
+                revents: 0,
+            };
+            let millis = libc::c_int::try_from(timeout.as_millis()).unwrap_or(libc::c_int::MAX);
+            // SAFETY: `pfd` is a single valid pollfd that outlives the call.
+            let ready = unsafe { libc::poll(&mut pfd, 1, millis) };
+            if ready < 0 {
+                return Err(io::Error::last_os_error());
+            }
+
+            let mut buf = [0u8; 4096];
+            loop {
+                match self.file.read(&mut buf) {
+                    Ok(0) => return Ok(()),
+                    Ok(_) => continue,
+                    Err(err) if err.kind() == io::ErrorKind::WouldBlock => return Ok(()),
+                    Err(err) => return Err(err),
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Tracker, STABLE_FOR};
+    use std::path::PathBuf;
+    use std::time::{Duration, Instant};
+
+    fn listing(files: &[(&str, Option<u64>)]) -> Vec<(PathBuf, Option<u64>)> {
+        files
+            .iter()
+            .map(|&(name, size)| (PathBuf::from(name), size))
+            .collect()
+    }
+
+    #[test]
+    fn waits_until_the_size_settles() {
+        let start = Instant::now();
+        let at = |secs: f64| start + Duration::from_secs_f64(secs);
+        let mut tracker = Tracker::new(&[]);
+
+        assert!(tracker
+            .observe(&listing(&[("a.zip", Some(10))]), at(0.0))
+            .is_empty());
+        assert!(tracker.has_pending());
+        // Still growing: the two seconds start over.
+        assert!(tracker
+            .observe(&listing(&[("a.zip", Some(20))]), at(1.5))
+            .is_empty());
+        assert!(tracker
+            .observe(&listing(&[("a.zip", Some(20))]), at(3.0))
+            .is_empty());
+        assert_eq!(
+            tracker.observe(&listing(&[("a.zip", Some(20))]), at(1.5) + STABLE_FOR),
+            [PathBuf::from("a.zip")]
+        );
+        assert!(!tracker.has_pending());
+    }
+
+    #[test]
+    fn ignores_existing_handled_and_unreadable_files() {
+        let start = Instant::now();
+        let later = start + STABLE_FOR;
+        let mut tracker = Tracker::new(&[PathBuf::from("old.txt")]);
+        let files = listing(&[
+            ("old.txt", Some(1)),
+            ("locked.txt", None),
+            ("new.txt", Some(1)),
+        ]);
+
+        assert!(tracker.observe(&files, start).is_empty());
+        assert_eq!(tracker.observe(&files, later), [PathBuf::from("new.txt")]);
+
+        tracker.mark_known([PathBuf::from("new.txt")]);
+        assert!(tracker.observe(&files, later + STABLE_FOR).is_empty());
+        assert!(!tracker.has_pending());
+    }
+
+    #[test]
+    fn forgets_files_that_disappear() {
+        let start = Instant::now();
+        let mut tracker = Tracker::new(&[PathBuf::from("a.txt")]);
+        assert!(tracker
+            .observe(&listing(&[("b.txt", Some(1))]), start)
+            .is_empty());
+        assert!(tracker.observe(&[], start).is_empty());
+        assert!(!tracker.has_pending());
+
+        // A file that comes back under a known name is new again.
+        let back = listing(&[("a.txt", Some(1))]);
+        assert!(tracker.observe(&back, start).is_empty());
+        assert_eq!(
+            tracker.observe(&back, start + STABLE_FOR),
+            [PathBuf::from("a.txt")]
+        );
+    }
+}